@@ -0,0 +1,33 @@
+use crate::db;
+use crate::project::Project;
+use crate::result::{user_error_result, Result};
+use crate::tag::Tag;
+
+pub fn do_add_alias(project: &Project, alias: &Tag, tag: &Tag) -> Result<()> {
+    let conn = project.open_db_connection()?;
+
+    if db::Tag::by_name(&conn, alias.as_str())?.is_some() {
+        return user_error_result(format!(
+            "Tag {} already exists: use merge-tags to combine it with {}",
+            alias.as_str(),
+            tag.as_str()
+        ));
+    }
+
+    if let Some(x) = db::TagAlias::by_name(&conn, alias.as_str())? {
+        return user_error_result(format!(
+            "Alias {} already exists for tag ID {}",
+            x.name, x.tag_id
+        ));
+    }
+
+    let names = db::TagAlias::resolve_names(&conn, &vec![tag.as_str()])?;
+    let target = match db::Tag::by_name(&conn, &names[0])? {
+        Some(x) => x,
+        None => return user_error_result(format!("No tag found with name {}", tag.as_str())),
+    };
+
+    db::TagAlias::insert(&conn, alias.as_str(), target.id)?;
+    println!("Added alias {} for tag {}", alias.as_str(), target.name);
+    Ok(())
+}
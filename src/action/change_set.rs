@@ -0,0 +1,76 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+pub enum Change {
+    InsertFile(String),
+    UpdateFile(String),
+    CopyFile(String, String),
+    MoveFile(String, String),
+    MissingFile(String),
+    InsertTag(String),
+    DeleteTag(String),
+    AddTag(String, String),
+    AddContentTag(String, String),
+    RemoveTag(String, String),
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Self::InsertFile(location) => write!(f, "Insert file {}", location),
+            Self::UpdateFile(location) => write!(f, "Update file {}", location),
+            Self::CopyFile(location, original) => {
+                write!(f, "Insert file {} as copy of {}", location, original)
+            }
+            Self::MoveFile(from, to) => write!(f, "Move file {} -> {}", from, to),
+            Self::MissingFile(location) => write!(f, "Mark file {} as missing", location),
+            Self::InsertTag(name) => write!(f, "Insert tag {}", name),
+            Self::DeleteTag(name) => write!(f, "Delete tag {}", name),
+            Self::AddTag(tag, location) => write!(f, "Add tag {} to {}", tag, location),
+            Self::AddContentTag(tag, location) => {
+                write!(f, "Add tag {} to content of {}", tag, location)
+            }
+            Self::RemoveTag(tag, location) => write!(f, "Remove tag {} from {}", tag, location),
+        }
+    }
+}
+
+// Changes collected by an action run with --dry-run so that they can be reviewed
+// before the action is run for real
+pub struct ChangeSet {
+    enabled: bool,
+    changes: Vec<Change>,
+}
+
+impl ChangeSet {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled: enabled,
+            changes: Vec::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn push(&mut self, change: Change) {
+        if self.enabled {
+            self.changes.push(change)
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        self.changes.iter().map(|x| x.to_string()).collect()
+    }
+
+    pub fn show(&self) {
+        if self.changes.is_empty() {
+            println!("Dry run: no changes would be made");
+        } else {
+            println!("Dry run: the following changes would be made:");
+            for change in &self.changes {
+                println!("  {}", change);
+            }
+        }
+    }
+}
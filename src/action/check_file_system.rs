@@ -1,50 +1,89 @@
+use std::cell::RefCell;
+use std::collections::hash_map::{Entry, HashMap};
+use std::path::Path;
+
 use crate::db;
-use crate::file_info::FileInfo;
+use crate::failure::{check_failures, show_failures, Failure};
+use crate::location::Location;
 use crate::project::Project;
 use crate::result::Result;
 use crate::sample_visitor;
+use crate::signature::{QuickSignature, Signature};
 
 pub fn do_check_file_system(project: &Project) -> Result<()> {
     println!("Checking {}", project.dir.display());
 
     let conn = project.open_db_connection()?;
-    sample_visitor::visit(&project.dir, project.path_checker(), &|entry| {
-        let p = entry.path();
-        let file_info = FileInfo::from_file(&project.dir, &p)?;
-        let rel_path = p.strip_prefix(&project.dir)?;
-        let mut has_error = false;
-        let mut message_shown = false;
+    let path_checker = project.path_checker(&conn)?;
+    let failures = RefCell::new(Vec::new());
+    sample_visitor::visit(
+        &project.dir,
+        &path_checker,
+        &|entry| {
+            let p = entry.path();
+            let rel_path = p.strip_prefix(&project.dir)?;
+            let location = Location::from_path(&project.dir, &p)?;
+            let quick_signature = QuickSignature::from_file(&p)?;
 
-        match db::File::by_location(&conn, &file_info.location)? {
-            Some(x) => {
-                if !x.signature.eq(&file_info.signature) {
-                    println!(
-                        "File {} is tracked but its signature has changed",
-                        rel_path.display()
-                    );
-                    has_error = true;
-                    message_shown = true;
+            // Full signature is only computed when quick signature cannot rule out a match
+            match db::File::by_location(&conn, &location)? {
+                Some(x) => {
+                    let changed = match &x.quick_signature {
+                        Some(q) if *q != quick_signature => true,
+                        _ => {
+                            Signature::from_file_with_scope(
+                                &p,
+                                x.signature.algorithm(),
+                                x.signature.scope(),
+                            )? != x.signature
+                        }
+                    };
+                    if changed {
+                        println!(
+                            "File {} is tracked but its signature has changed",
+                            rel_path.display()
+                        );
+                    }
                 }
-            }
-            None => has_error = true,
-        };
-
-        match db::File::by_signature(&conn, &file_info.signature)? {
-            Some(x) => {
-                if !x.location.eq(&file_info.location) {
-                    println!("File {} is not tracked and has a signature matching an existing item in the database", rel_path.display());
-                    has_error = true;
-                    message_shown = true;
+                None => {
+                    let candidates = db::File::all_by_quick_signature(&conn, &quick_signature)?;
+                    match find_copy(&p, &candidates)? {
+                        Some(x) => println!(
+                            "File {} is not tracked and has the same content as {}",
+                            rel_path.display(),
+                            x.location.as_str()
+                        ),
+                        None => println!("File not tracked in database: {}", rel_path.display()),
+                    }
                 }
-            }
-            None => has_error = true,
-        };
+            };
 
-        if has_error && !message_shown {
-            println!("File not tracked in database: {}", rel_path.display())
-        }
+            Ok(())
+        },
+        &|path, error| {
+            failures.borrow_mut().push(Failure::new(path, error));
+            Ok(())
+        },
+    )?;
 
-        Ok(())
-    })?;
-    Ok(())
+    let failures = failures.into_inner();
+    show_failures(&failures);
+    check_failures(&failures)
+}
+
+// Candidates may have been signed with different algorithms or scopes so file is hashed
+// once for each combination
+fn find_copy<'a>(path: &Path, candidates: &'a Vec<db::File>) -> Result<Option<&'a db::File>> {
+    let mut signatures = HashMap::new();
+    for candidate in candidates {
+        let key = (candidate.signature.algorithm(), candidate.signature.scope());
+        let signature = match signatures.entry(key) {
+            Entry::Occupied(x) => x.into_mut(),
+            Entry::Vacant(x) => x.insert(Signature::from_file_with_scope(path, key.0, key.1)?),
+        };
+        if *signature == candidate.signature {
+            return Ok(Some(candidate));
+        }
+    }
+    Ok(None)
 }
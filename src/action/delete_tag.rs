@@ -1,11 +1,36 @@
+use super::change_set::{Change, ChangeSet};
 use crate::db;
 use crate::project::Project;
 use crate::result::Result;
 use crate::tag::Tag;
 
-pub fn do_delete_tag(project: &Project, tags: &Vec<Tag>) -> Result<()> {
-    let conn = project.open_db_connection()?;
+pub fn do_delete_tag(project: &Project, tags: &Vec<Tag>, dry_run: bool) -> Result<()> {
+    let mut conn = project.open_db_connection()?;
+    let tx = conn.transaction()?;
     let names = tags.into_iter().map(|x| x.as_str()).collect();
-    db::Tag::delete_by_names(&conn, &names)?;
+    let names = db::TagAlias::resolve_names(&tx, &names)?;
+    let names = names.iter().map(|x| x.as_str()).collect();
+
+    let mut changes = ChangeSet::new(dry_run);
+    if changes.is_enabled() {
+        for tag in db::Tag::all_by_names(&tx, &names)? {
+            for file in db::File::all_by_tag_id(&tx, tag.id)? {
+                changes.push(Change::RemoveTag(
+                    tag.name.clone(),
+                    String::from(file.location.as_str()),
+                ));
+            }
+            changes.push(Change::DeleteTag(tag.name));
+        }
+    }
+
+    db::Tag::delete_by_names(&tx, &names)?;
+
+    if dry_run {
+        changes.show();
+        tx.rollback()?;
+    } else {
+        tx.commit()?;
+    }
     Ok(())
 }
@@ -0,0 +1,43 @@
+use crate::project::Project;
+use crate::result::{user_error_result, Result};
+
+pub fn do_filters(project: &Project, include: Option<&str>, exclude: Option<&str>) -> Result<()> {
+    let conn = project.open_db_connection()?;
+
+    if let Some(s) = include {
+        // Extensions may be given with or without leading dot
+        let extensions = parse_list(s)?
+            .into_iter()
+            .map(|x| String::from(x.trim_start_matches('.')))
+            .collect::<Vec<_>>();
+        project.set_include_extensions(&conn, &extensions)?;
+    }
+    if let Some(s) = exclude {
+        project.set_exclude_directories(&conn, &parse_list(s)?)?;
+    }
+
+    println!(
+        "Include extensions: {}",
+        project.include_extensions(&conn)?.join(", ")
+    );
+    println!(
+        "Exclude directories: {}",
+        project.exclude_directories(&conn)?.join(", ")
+    );
+    Ok(())
+}
+
+// Empty string yields empty list which restores default
+fn parse_list(s: &str) -> Result<Vec<String>> {
+    if s.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let values = s.split(',').map(|x| x.trim()).collect::<Vec<_>>();
+    if values
+        .iter()
+        .any(|x| x.is_empty() || x.contains('/') || x.contains('\\'))
+    {
+        return user_error_result(format!("Invalid list \"{}\"", s));
+    }
+    Ok(values.into_iter().map(String::from).collect())
+}
@@ -0,0 +1,29 @@
+use itertools::Itertools;
+use std::collections::HashMap;
+
+use crate::db;
+use crate::like::Like;
+use crate::project::Project;
+use crate::result::Result;
+
+pub fn do_list_aliases(project: &Project, like: Option<Like>) -> Result<()> {
+    let conn = project.open_db_connection()?;
+
+    println!("Project directory: {}", project.dir.display());
+    println!("Database path: {}", project.db_path.display());
+
+    let tags = db::Tag::all(&conn, None)?
+        .into_iter()
+        .map(|x| (x.id, x.name))
+        .collect::<HashMap<_, _>>();
+
+    println!("Aliases:");
+    for alias in db::TagAlias::all(&conn, like)?
+        .iter()
+        .sorted_by_key(|&x| &x.name)
+    {
+        println!("  {} -> {}", alias.name, tags[&alias.tag_id]);
+    }
+
+    Ok(())
+}
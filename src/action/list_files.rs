@@ -5,7 +5,13 @@ use crate::like::Like;
 use crate::project::Project;
 use crate::result::Result;
 
-pub fn do_list_files(project: &Project, like: Option<Like>) -> Result<()> {
+// Sizes are those recorded by last scan so that files are not read
+pub fn do_list_files(
+    project: &Project,
+    like: Option<Like>,
+    min_size: Option<i64>,
+    max_size: Option<i64>,
+) -> Result<()> {
     let conn = project.open_db_connection()?;
 
     println!("Project directory: {}", project.dir.display());
@@ -14,9 +20,20 @@ pub fn do_list_files(project: &Project, like: Option<Like>) -> Result<()> {
     println!("Files:");
     for file in db::File::all(&conn, like)?
         .iter()
+        .filter(|x| match x.stat {
+            Some(stat) => {
+                min_size.map_or(true, |n| stat.size >= n)
+                    && max_size.map_or(true, |n| stat.size <= n)
+            }
+            None => min_size.is_none() && max_size.is_none(),
+        })
         .sorted_by_key(|&x| x.location.as_str())
     {
-        println!("  {}", file.location.as_str());
+        if file.missing {
+            println!("  {} (missing)", file.location.as_str());
+        } else {
+            println!("  {}", file.location.as_str());
+        }
     }
 
     Ok(())
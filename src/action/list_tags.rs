@@ -1,23 +1,84 @@
 use itertools::Itertools;
+use std::collections::{HashMap, HashSet};
 
+use crate::color::colorize;
 use crate::db;
 use crate::like::Like;
 use crate::project::Project;
 use crate::result::Result;
+use crate::tag;
 
-pub fn do_list_tags(project: &Project, like: Option<Like>) -> Result<()> {
+pub fn do_list_tags(project: &Project, like: Option<Like>, tree: bool) -> Result<()> {
     let conn = project.open_db_connection()?;
 
     println!("Project directory: {}", project.dir.display());
     println!("Database path: {}", project.db_path.display());
 
     println!("Tags:");
-    for tag in db::Tag::all(&conn, like)?
-        .iter()
-        .sorted_by_key(|&x| &x.name)
+    let tags = db::Tag::all(&conn, like)?;
+    let mut aliases = HashMap::<_, Vec<_>>::new();
+    for alias in db::TagAlias::all(&conn, None)?
+        .into_iter()
+        .sorted_by_key(|x| x.name.clone())
     {
-        println!("  {}", tag.name);
+        aliases.entry(alias.tag_id).or_default().push(alias.name);
+    }
+
+    if tree {
+        show_tree(&tags, &aliases);
+    } else {
+        for tag in tags.iter().sorted_by_key(|&x| &x.name) {
+            println!("  {}", format_tag(&tag.name, tag, &aliases));
+        }
     }
 
     Ok(())
 }
+
+fn format_tag(name: &str, tag: &db::Tag, aliases: &HashMap<i64, Vec<String>>) -> String {
+    let mut s = colorize(name, &tag.color);
+    if let Some(category) = &tag.category {
+        s.push_str(&format!(" [{}]", category));
+    }
+    if let Some(names) = aliases.get(&tag.id) {
+        s.push_str(&format!(" (aliases: {})", names.join(", ")));
+    }
+    if let Some(description) = &tag.description {
+        s.push_str(&format!(": {}", description));
+    }
+    s
+}
+
+fn show_tree(tags: &Vec<db::Tag>, aliases: &HashMap<i64, Vec<String>>) {
+    let ids = tags.iter().map(|x| x.id).collect::<HashSet<_>>();
+    let mut children = HashMap::<_, Vec<_>>::new();
+    for tag in tags {
+        // Tags whose parent is not in the list are shown at the top level
+        let parent_id = tag.parent_id.filter(|x| ids.contains(x));
+        children.entry(parent_id).or_default().push(tag);
+    }
+    show_subtree(&children, aliases, None, 1);
+}
+
+fn show_subtree(
+    children: &HashMap<Option<i64>, Vec<&db::Tag>>,
+    aliases: &HashMap<i64, Vec<String>>,
+    parent_id: Option<i64>,
+    depth: usize,
+) {
+    if let Some(tags) = children.get(&parent_id) {
+        for tag in tags.iter().sorted_by_key(|&x| &x.name) {
+            let name = match parent_id {
+                Some(_) => tag::Tag::from(&tag.name).leaf_name().to_string(),
+                None => tag.name.clone(),
+            };
+            println!(
+                "{:indent$}{}",
+                "",
+                format_tag(&name, tag, aliases),
+                indent = depth * 2
+            );
+            show_subtree(children, aliases, Some(tag.id), depth + 1);
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use crate::db;
+use crate::project::Project;
+use crate::result::{user_error_result, Result};
+use crate::tag::Tag;
+
+pub fn do_merge_tags(project: &Project, source_tags: &Vec<Tag>, target_tag: &Tag) -> Result<()> {
+    let mut conn = project.open_db_connection()?;
+    let tx = conn.transaction()?;
+
+    // Aliases are resolved so that merging into an alias merges into its tag
+    let target_names = db::TagAlias::resolve_names(&tx, &vec![target_tag.as_str()])?;
+    let target_tag = Tag::from(target_names[0].as_str());
+    let source_names = db::TagAlias::resolve_names(
+        &tx,
+        &source_tags.iter().map(|x| x.as_str()).collect::<Vec<_>>(),
+    )?;
+
+    let mut sources = Vec::new();
+    for (source_tag, source_name) in source_tags.iter().zip(&source_names) {
+        if source_name == target_tag.as_str() {
+            continue;
+        }
+        match db::Tag::by_name(&tx, source_name)? {
+            Some(x) => {
+                if !db::Tag::children(&tx, x.id)?.is_empty() {
+                    return user_error_result(format!(
+                        "Tag {} has child tags: merge or rename them first",
+                        x.name
+                    ));
+                }
+                sources.push(x)
+            }
+            None => {
+                return user_error_result(format!("No tag found with name {}", source_tag.as_str()))
+            }
+        }
+    }
+
+    let target_id = db::Tag::upsert(&tx, &target_tag)?;
+
+    for source in &sources {
+        let count = db::FileTag::reassign(&tx, source.id, target_id)?
+            + db::ContentTag::reassign(&tx, source.id, target_id)?;
+        db::Tag::delete_by_id(&tx, source.id)?;
+        println!(
+            "Merged tag {} ({} files) into {}",
+            source.name,
+            count,
+            target_tag.as_str()
+        );
+    }
+
+    tx.commit()?;
+    Ok(())
+}
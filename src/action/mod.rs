@@ -1,21 +1,47 @@
+mod add_alias;
+mod change_set;
 mod check_database;
 mod check_file_system;
 mod default;
 mod delete_tag;
+mod filters;
+mod list_aliases;
 mod list_files;
 mod list_tags;
+mod merge_tags;
+mod prune;
+mod rehash;
+mod remove_alias;
+mod rename_tag;
 mod scan;
 mod search;
 mod show_file;
+mod similar;
 mod tag;
+mod tag_info;
+mod untag;
+mod util;
+mod verify;
 
+pub use self::add_alias::do_add_alias;
 pub use self::check_database::do_check_database;
 pub use self::check_file_system::do_check_file_system;
 pub use self::default::do_default;
 pub use self::delete_tag::do_delete_tag;
+pub use self::filters::do_filters;
+pub use self::list_aliases::do_list_aliases;
 pub use self::list_files::do_list_files;
 pub use self::list_tags::do_list_tags;
+pub use self::merge_tags::do_merge_tags;
+pub use self::prune::do_prune;
+pub use self::rehash::do_rehash;
+pub use self::remove_alias::do_remove_alias;
+pub use self::rename_tag::do_rename_tag;
 pub use self::scan::do_scan;
 pub use self::search::do_search;
 pub use self::show_file::do_show_file;
+pub use self::similar::do_similar;
 pub use self::tag::do_tag;
+pub use self::tag_info::{do_tag_info, TagInfoUpdate};
+pub use self::untag::do_untag;
+pub use self::verify::do_verify;
@@ -0,0 +1,65 @@
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::path::Path;
+
+use super::util::get_locations_within_project;
+use crate::db;
+use crate::project::Project;
+use crate::result::Result;
+use crate::util::{unix_time_now, SECONDS_PER_DAY};
+
+pub fn do_prune(
+    project: &Project,
+    paths: &Vec<impl AsRef<Path> + Debug>,
+    older_than_days: i64,
+) -> Result<()> {
+    // Paths of missing files need not exist
+    let within = get_locations_within_project(project, paths)?;
+
+    let mut conn = project.open_db_connection()?;
+    let tx = conn.transaction()?;
+
+    let last_seen_before = unix_time_now()? - older_than_days * SECONDS_PER_DAY;
+    let files = db::File::all_missing(&tx, last_seen_before, &within)?;
+
+    let mut affected_tags = HashSet::new();
+    for file in &files {
+        let tags = db::Tag::all_by_file_id(&tx, file.id)?;
+        if tags.is_empty() {
+            println!("Pruned file: {}", file.location.as_str());
+        } else {
+            println!(
+                "Pruned file: {} (tags: {})",
+                file.location.as_str(),
+                tags.iter()
+                    .map(|x| x.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        db::File::delete_by_id(&tx, file.id)?;
+        affected_tags.extend(tags.into_iter().map(|x| (x.id, x.name)));
+    }
+
+    // Content tags only go once last copy of content has been pruned
+    db::Content::delete_unused(&tx)?;
+
+    let mut lost_tags = Vec::new();
+    for (tag_id, tag_name) in affected_tags {
+        if db::FileTag::count_by_tag_id(&tx, tag_id)?
+            + db::ContentTag::count_by_tag_id(&tx, tag_id)?
+            == 0
+        {
+            lost_tags.push(tag_name);
+        }
+    }
+    lost_tags.sort();
+
+    tx.commit()?;
+
+    for tag_name in &lost_tags {
+        println!("Tag {} is no longer applied to any files", tag_name);
+    }
+    println!("Pruned {} missing files", files.len());
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use crate::db;
+use crate::failure::{check_failures, show_failures, Failure};
+use crate::file_info::FileStat;
+use crate::progress::Progress;
+use crate::project::Project;
+use crate::result::{user_error, Result};
+use crate::signature::{Algorithm, Scope, Signature};
+
+pub fn do_rehash(project: &Project, algorithm: Algorithm) -> Result<()> {
+    let mut conn = project.open_db_connection()?;
+    let tx = conn.transaction()?;
+
+    // Whole-file signatures may need upgrading to audio payload signatures even if they
+    // were computed with given algorithm
+    let contents = db::Content::all(&tx)?
+        .into_iter()
+        .filter(|x| x.signature.algorithm() != algorithm || x.signature.scope() == Scope::File)
+        .collect::<Vec<_>>();
+    let total_bytes = contents.iter().filter_map(|x| x.size).sum::<i64>();
+    let mut progress = Progress::new(contents.len() as u64, total_bytes as u64);
+
+    let mut rehashed = 0;
+    let mut failures = Vec::new();
+    for content in &contents {
+        let copies = db::File::all_by_signature(&tx, &content.signature)?;
+        let size = content.size.unwrap_or(0) as u64;
+
+        // Content is rehashed from any copy that is unchanged since it was last scanned
+        let copy = copies.iter().find(|x| {
+            !x.missing
+                && x.stat.is_some()
+                && FileStat::from_path(&x.location.to_path(&project.dir)).ok() == x.stat
+        });
+        let file = match copy {
+            Some(x) => x,
+            None => {
+                progress.update(size, 0);
+                if content.signature.algorithm() != algorithm {
+                    if let Some(x) = copies.first() {
+                        failures.push(Failure::new(
+                            &x.location.to_path(&project.dir),
+                            user_error("No unchanged copy of file found: run scan first"),
+                        ));
+                    }
+                }
+                continue;
+            }
+        };
+
+        let path = file.location.to_path(&project.dir);
+        if content.signature.algorithm() == algorithm
+            && Signature::scope_of_file(&path).ok() == Some(Scope::File)
+        {
+            progress.update(size, 0);
+            continue;
+        }
+
+        match Signature::from_file(&path, algorithm) {
+            Ok(signature) => {
+                // Files whose signatures only differed in metadata now share content
+                match db::Content::by_signature(&tx, &signature)? {
+                    Some(x) => db::Content::merge(&tx, content.id, x.id)?,
+                    None => db::Content::update_signature(&tx, content.id, &signature)?,
+                };
+                rehashed += 1;
+                progress.update(size, size);
+            }
+            Err(e) => {
+                progress.update(size, 0);
+                failures.push(Failure::new(&path, e));
+            }
+        }
+    }
+    progress.finish();
+
+    project.set_signature_algorithm(&tx, algorithm)?;
+    tx.commit()?;
+
+    show_failures(&failures);
+    println!("Rehashed {} files using {}", rehashed, algorithm.as_str());
+    check_failures(&failures)
+}
@@ -0,0 +1,24 @@
+use crate::db;
+use crate::project::Project;
+use crate::result::{user_error_result, Result};
+use crate::tag::Tag;
+
+pub fn do_remove_alias(project: &Project, aliases: &Vec<Tag>) -> Result<()> {
+    let mut conn = project.open_db_connection()?;
+    let tx = conn.transaction()?;
+
+    // All aliases are checked first so that none are removed if any is unknown
+    for alias in aliases {
+        if db::TagAlias::by_name(&tx, alias.as_str())?.is_none() {
+            return user_error_result(format!("No alias found with name {}", alias.as_str()));
+        }
+    }
+
+    for alias in aliases {
+        db::TagAlias::delete_by_name(&tx, alias.as_str())?;
+        println!("Removed alias {}", alias.as_str());
+    }
+
+    tx.commit()?;
+    Ok(())
+}
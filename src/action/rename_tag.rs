@@ -0,0 +1,91 @@
+use crate::db;
+use crate::project::Project;
+use crate::result::{user_error_result, Result};
+use crate::tag::Tag;
+
+pub fn do_rename_tag(project: &Project, old_tag: &Tag, new_tag: &Tag) -> Result<()> {
+    let mut conn = project.open_db_connection()?;
+    let tx = conn.transaction()?;
+
+    let names = db::TagAlias::resolve_names(&tx, &vec![old_tag.as_str()])?;
+    let tag = match db::Tag::by_name(&tx, &names[0])? {
+        Some(x) => x,
+        None => return user_error_result(format!("No tag found with name {}", old_tag.as_str())),
+    };
+
+    if new_tag
+        .as_str()
+        .starts_with(&format!("{}{}", tag.name.as_str(), Tag::SEPARATOR))
+    {
+        return user_error_result(format!(
+            "Tag {} cannot be renamed to its own descendant {}",
+            tag.name.as_str(),
+            new_tag.as_str()
+        ));
+    }
+
+    // Descendants are renamed along with tag so their new names must be free too
+    let mut new_names = vec![(tag.name.clone(), String::from(new_tag.as_str()))];
+    for descendant in db::Tag::descendants(&tx, tag.name.as_str())? {
+        let new_name = format!(
+            "{}{}",
+            new_tag.as_str(),
+            &descendant.name[tag.name.as_str().len()..]
+        );
+        new_names.push((descendant.name, new_name));
+    }
+
+    for (old_name, new_name) in &new_names {
+        if db::TagAlias::by_name(&tx, new_name)?.is_some() {
+            return user_error_result(format!(
+                "{} is already an alias: remove it with remove-alias first",
+                new_name
+            ));
+        }
+
+        if db::Tag::by_name(&tx, new_name)?.is_some() {
+            return user_error_result(format!(
+                "Tag {} already exists: use merge-tags to combine it with {}",
+                new_name, old_name
+            ));
+        }
+    }
+
+    db::Tag::rename(&tx, &tag, new_tag)?;
+    tx.commit()?;
+
+    println!("Renamed tag {} to {}", tag.name.as_str(), new_tag.as_str());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::result::Error;
+    use crate::test_util::TempPath;
+
+    #[test]
+    fn test_rename_tag_descendant_conflict() -> Result<()> {
+        let temp = TempPath::new("rename-tag");
+        fs::create_dir_all(temp.path())?;
+        let project = Project::from_dir(temp.path());
+        let conn = project.open_db_connection()?;
+        db::Tag::upsert(&conn, &Tag::from("a/x"))?;
+        db::Tag::upsert(&conn, &Tag::from("a/y"))?;
+        // Tag whose parent does not exist as left by databases created before tags were
+        // hierarchical
+        conn.execute(
+            "INSERT INTO tags (name) VALUES ('b/x')",
+            rusqlite::NO_PARAMS,
+        )?;
+
+        let result = do_rename_tag(&project, &Tag::from("a"), &Tag::from("b"));
+
+        assert!(db::Tag::by_name(&conn, "b")?.is_none());
+        assert!(matches!(result, Err(Error::User(_))));
+        assert!(db::Tag::by_name(&conn, "a/y")?.is_some());
+        Ok(())
+    }
+}
@@ -1,32 +1,499 @@
+use rusqlite::Connection;
+use std::collections::HashSet;
+use std::path::PathBuf;
 use std::time::Instant;
 
-use crate::db;
+use super::change_set::{Change, ChangeSet};
+use super::util::get_locations_within_project;
+use crate::db::{self, UpsertOutcome};
+use crate::failure::{check_failures, show_failures, Failure};
 use crate::file_info::FileInfo;
+use crate::location::Location;
+use crate::progress::Progress;
 use crate::project::Project;
-use crate::result::{Error, Result};
-use crate::sample_visitor;
+use crate::result::{user_error_result, Result};
+use crate::sample_visitor::PathChecker;
+use crate::scanner::{self, KnownFile, ScanItem, ScanOptions};
+use crate::signature::{Scope, Signature};
+use crate::util::unix_time_now;
 
-pub fn do_scan(project: &Project) -> Result<()> {
+// Number of files written per transaction
+const BATCH_SIZE: usize = 1000;
+
+#[derive(Default)]
+struct ScanSummary {
+    new: usize,
+    updated: usize,
+    unchanged: usize,
+    duplicate: usize,
+    moved: usize,
+    missing: usize,
+    errored: usize,
+    bytes_hashed: u64,
+    elapsed_secs: u64,
+}
+
+impl ScanSummary {
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "new": self.new,
+            "updated": self.updated,
+            "unchanged": self.unchanged,
+            "duplicate": self.duplicate,
+            "moved": self.moved,
+            "missing": self.missing,
+            "errored": self.errored,
+            "bytes_hashed": self.bytes_hashed,
+            "elapsed_secs": self.elapsed_secs,
+        })
+    }
+
+    fn show(&self) {
+        println!("Scan summary:");
+        println!("  New:       {}", self.new);
+        println!("  Updated:   {}", self.updated);
+        println!("  Unchanged: {}", self.unchanged);
+        println!("  Duplicate: {}", self.duplicate);
+        println!("  Moved:     {}", self.moved);
+        println!("  Missing:   {}", self.missing);
+        println!("  Errored:   {}", self.errored);
+        println!(
+            "Rebuild operation completed in {} seconds",
+            self.elapsed_secs
+        );
+    }
+}
+
+pub fn do_scan(
+    project: &Project,
+    paths: &Vec<PathBuf>,
+    options: &ScanOptions,
+    json: bool,
+    dry_run: bool,
+) -> Result<()> {
     let start = Instant::now();
+    let locations = get_scan_locations(project, paths)?;
+    let paths = locations
+        .iter()
+        .map(|x| x.to_path(&project.dir))
+        .collect::<Vec<_>>();
     let conn = project.open_db_connection()?;
-    sample_visitor::visit(&project.dir, project.path_checker(), &|entry| {
-        let file_info = FileInfo::from_file(&project.dir, &entry.path())?;
-        match db::File::upsert(&conn, &file_info) {
-            Ok(_) => {}
-            Err(Error::Internal("Rusqlite", _)) => {
-                if db::DuplicateFile::upsert(&conn, &file_info)? != 0 {
+
+    let files = db::File::all(&conn, None)?;
+
+    // Files signed whole before their format was signed by audio payload can only be
+    // matched by size without hashing them again
+    let whole_file_sizes = files
+        .iter()
+        .filter(|x| x.signature.scope() == Scope::File)
+        .map(|x| x.signature.size())
+        .collect::<HashSet<_>>();
+
+    // Files no longer matched by filters are not looked for and may still exist
+    let path_checker = project.path_checker(&conn)?;
+    let mut unmatched_locations = Vec::new();
+    for file in &files {
+        if !path_checker.matches(&file.location.to_path(&project.dir))? {
+            unmatched_locations.push(file.location.clone());
+        }
+    }
+
+    let known = files
+        .into_iter()
+        .filter_map(|x| match x.stat {
+            Some(stat) => Some((
+                x.location,
+                KnownFile {
+                    stat: stat,
+                    has_quick_signature: x.quick_signature.is_some(),
+                },
+            )),
+            None => None,
+        })
+        .collect();
+    let algorithm = project.signature_algorithm(&conn)?;
+
+    // Pre-count pass is only worthwhile if progress can be displayed
+    let mut progress = if Progress::is_supported() {
+        let (total_files, total_bytes) = scanner::count_files(&paths, &path_checker)?;
+        Progress::new(total_files, total_bytes)
+    } else {
+        Progress::disabled()
+    };
+
+    // Dry run performs all writes in a single transaction that is then rolled back
+    let mut batch = db::Batch::new(&conn, if dry_run { usize::MAX } else { BATCH_SIZE })?;
+    let mut summary = ScanSummary::default();
+    let mut changes = ChangeSet::new(dry_run);
+    let verbose = !json && !dry_run;
+    let mut seen_locations = Vec::new();
+    let mut failures = Vec::new();
+    scanner::scan(
+        &project.dir,
+        &paths,
+        path_checker,
+        known,
+        algorithm,
+        options,
+        &mut |path, result| {
+            let result = result.and_then(|x| {
+                let whole_file_signature = get_whole_file_signature(&x, &whole_file_sizes)?;
+                Ok((x, whole_file_signature))
+            });
+            let (item, whole_file_signature) = match result {
+                Ok(x) => x,
+                Err(e) => {
+                    progress.update(0, 0);
+                    failures.push(Failure::new(path, e));
+                    return Ok(());
+                }
+            };
+            let size = item.stat.size as u64;
+            let hashed = if item.signature.is_some() { size } else { 0 };
+            progress.update(size, hashed);
+            summary.bytes_hashed += hashed;
+            seen_locations.push(item.location.clone());
+            // Database errors abort scan so that current batch is rolled back
+            write_item(
+                batch.conn(),
+                project,
+                item,
+                whole_file_signature,
+                &mut summary,
+                &mut changes,
+                verbose,
+            )?;
+            batch.tick()
+        },
+    )?;
+    progress.finish();
+
+    // Files that could not be read, and anything underneath directories that could not
+    // be read, may still exist and must not be marked as missing
+    let mut excluded_locations = failures
+        .iter()
+        .filter_map(|x| Location::from_path(&project.dir, &x.path).ok())
+        .collect::<Vec<_>>();
+    excluded_locations.extend(unmatched_locations);
+
+    db::File::mark_seen(batch.conn(), &seen_locations, unix_time_now()?)?;
+    let missing_files = db::File::mark_missing_except(
+        batch.conn(),
+        &seen_locations,
+        &locations,
+        &excluded_locations,
+    )?;
+
+    // Content replaced by updated files is no longer found anywhere
+    db::Content::delete_unused(batch.conn())?;
+    if dry_run {
+        batch.rollback()?;
+    } else {
+        batch.commit()?;
+    }
+
+    summary.missing = missing_files.len();
+    summary.errored = failures.len();
+    summary.elapsed_secs = start.elapsed().as_secs();
+
+    for file in &missing_files {
+        if verbose {
+            println!("Missing file: {}", file.location.as_str());
+        }
+        changes.push(Change::MissingFile(String::from(file.location.as_str())));
+    }
+
+    if changes.is_enabled() && !json {
+        changes.show();
+    }
+
+    if json {
+        let mut value = summary.to_json();
+        value["failures"] = failures.iter().map(|x| x.to_json()).collect();
+        if changes.is_enabled() {
+            value["changes"] = changes.to_json();
+        }
+        println!("{}", value);
+    } else {
+        show_failures(&failures);
+        summary.show();
+    }
+    check_failures(&failures)
+}
+
+// Resolves paths to scan which, unlike paths to prune, must exist
+fn get_scan_locations(project: &Project, paths: &Vec<PathBuf>) -> Result<Vec<Location>> {
+    let locations = get_locations_within_project(project, paths)?;
+    if let Some(path) = paths.iter().find(|x| !x.exists()) {
+        return user_error_result(format!("Path {} does not exist", path.display()));
+    }
+    Ok(locations)
+}
+
+// Signature of new audio file computed the way a copy of it scanned before audio
+// payloads were signed separately would have been
+fn get_whole_file_signature(
+    item: &ScanItem,
+    whole_file_sizes: &HashSet<u64>,
+) -> Result<Option<Signature>> {
+    match &item.signature {
+        Some(x)
+            if x.scope() == Scope::Audio && whole_file_sizes.contains(&(item.stat.size as u64)) =>
+        {
+            Ok(Some(Signature::from_file_with_scope(
+                &item.path,
+                x.algorithm(),
+                Scope::File,
+            )?))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn write_item(
+    conn: &Connection,
+    project: &Project,
+    item: ScanItem,
+    whole_file_signature: Option<Signature>,
+    summary: &mut ScanSummary,
+    changes: &mut ChangeSet,
+    verbose: bool,
+) -> Result<()> {
+    let signature = match item.signature {
+        Some(x) => x,
+        None => {
+            if let Some(quick_signature) = item.quick_signature {
+                db::File::update_quick_signature(conn, &item.location, &quick_signature)?;
+            }
+            summary.unchanged += 1;
+            return Ok(());
+        }
+    };
+
+    let mut file_info = FileInfo::new(item.location, signature);
+    file_info.stat = Some(item.stat);
+    file_info.quick_signature = item.quick_signature;
+
+    // Copies scanned before audio payloads were signed separately have signatures
+    // covering whole file and copy shares content and therefore tags with them
+    if let Some(signature) = whole_file_signature {
+        if db::Content::by_signature(conn, &file_info.signature)?.is_none()
+            && db::Content::by_signature(conn, &signature)?.is_some()
+        {
+            file_info.signature = signature;
+        }
+    }
+
+    match db::File::upsert(conn, &file_info)? {
+        UpsertOutcome::Inserted(_) => {
+            changes.push(Change::InsertFile(String::from(
+                file_info.location.as_str(),
+            )));
+            summary.new += 1
+        }
+        UpsertOutcome::Copied { id, copies } => {
+            // A known copy that has vanished from its old location has been moved
+            if let Some(file) = copies
+                .iter()
+                .find(|x| !x.location.to_path(&project.dir).exists())
+            {
+                db::File::delete_by_id(conn, id)?;
+                db::File::update_location(conn, file.id, &file_info)?;
+                if verbose {
+                    println!(
+                        "Moved file: {} -> {}",
+                        file.location.as_str(),
+                        file_info.location.as_str()
+                    );
+                }
+                changes.push(Change::MoveFile(
+                    String::from(file.location.as_str()),
+                    String::from(file_info.location.as_str()),
+                ));
+                summary.moved += 1;
+            } else {
+                let original = &copies[0];
+                if verbose {
                     println!(
-                        "Duplicate file location and/or signature: {}, {}",
-                        file_info.location.as_str(),
-                        file_info.signature.as_str()
-                    )
+                        "Copied file: {} -> {}",
+                        original.location.as_str(),
+                        file_info.location.as_str()
+                    );
                 }
+                changes.push(Change::CopyFile(
+                    String::from(file_info.location.as_str()),
+                    String::from(original.location.as_str()),
+                ));
+                summary.duplicate += 1;
             }
-            _ => {}
         }
-        Ok(())
-    })?;
-    let elapsed = start.elapsed().as_secs();
-    println!("Rebuild operation completed in {} seconds", elapsed);
+        UpsertOutcome::Updated {
+            previous_content_id,
+            ..
+        } => {
+            changes.push(Change::UpdateFile(String::from(
+                file_info.location.as_str(),
+            )));
+            summary.updated += 1;
+            carry_over_content_tags(
+                conn,
+                &file_info.location,
+                previous_content_id,
+                changes,
+                verbose,
+            )?;
+        }
+        UpsertOutcome::Unchanged(_) => summary.unchanged += 1,
+    }
     Ok(())
 }
+
+// Tags of content edited at its only location would otherwise be deleted along with the
+// content that is no longer found anywhere
+fn carry_over_content_tags(
+    conn: &Connection,
+    location: &Location,
+    previous_content_id: i64,
+    changes: &mut ChangeSet,
+    verbose: bool,
+) -> Result<()> {
+    let tags = db::Tag::all_by_content_id(conn, previous_content_id)?;
+    if tags.is_empty() {
+        return Ok(());
+    }
+    let content_id = match db::File::by_location(conn, location)? {
+        Some(x) => x.content_id,
+        None => return Ok(()),
+    };
+    if db::ContentTag::carry_over(conn, previous_content_id, content_id)? == 0 {
+        return Ok(());
+    }
+    for tag in tags {
+        if verbose {
+            println!(
+                "Kept tag {} on updated content of {}",
+                tag.name,
+                location.as_str()
+            );
+        }
+        changes.push(Change::AddContentTag(
+            tag.name,
+            String::from(location.as_str()),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use std::fs;
+
+    use super::*;
+    use crate::result::Error;
+    use crate::signature::Algorithm;
+    use crate::test_util::TempPath;
+
+    fn scan_options() -> ScanOptions {
+        ScanOptions {
+            jobs: 1,
+            full: false,
+        }
+    }
+
+    #[test]
+    fn test_scan_moves_whole_file_signed_audio() -> Result<()> {
+        let temp = TempPath::new("scan-whole-file");
+        fs::create_dir_all(temp.path())?;
+        let path = temp.path().join("new.wav");
+        fs::write(&path, b"RIFF\x10\0\0\0WAVEdata\x03\0\0\0abc\0")?;
+        let project = Project::from_dir(temp.path());
+        let conn = project.open_db_connection()?;
+        let signature = Signature::from_file_with_scope(&path, Algorithm::Sha256, Scope::File)?;
+        let id = db::File::insert(
+            &conn,
+            &FileInfo::new(Location::try_from("old.wav")?, signature),
+        )?;
+
+        do_scan(&project, &Vec::new(), &scan_options(), true, false)?;
+
+        let files = db::File::all(&conn, None)?;
+        assert_eq!(1, files.len());
+        assert_eq!(id, files[0].id);
+        assert_eq!("new.wav", files[0].location.as_str());
+        assert_eq!(Scope::File, files[0].signature.scope());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_scan_keeps_files_in_unreadable_directory() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = TempPath::new("scan-unreadable");
+        let dir = temp.path().join("locked");
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("sample.wav"), b"abc")?;
+        let project = Project::from_dir(temp.path());
+        do_scan(&project, &Vec::new(), &scan_options(), true, false)?;
+
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o000))?;
+        // Permissions do not apply to superuser
+        let readable = fs::read_dir(&dir).is_ok();
+        let result = if readable {
+            Ok(())
+        } else {
+            do_scan(&project, &Vec::new(), &scan_options(), true, false)
+        };
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755))?;
+        if readable {
+            return Ok(());
+        }
+
+        // Directory is reported as failure while file underneath it is not missing
+        assert!(matches!(result, Err(Error::User(_))));
+        let files = db::File::all(&project.open_db_connection()?, None)?;
+        assert_eq!(1, files.len());
+        assert!(!files[0].missing);
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_keeps_files_excluded_by_filters() -> Result<()> {
+        let temp = TempPath::new("scan-filters");
+        fs::create_dir_all(temp.path())?;
+        fs::write(temp.path().join("a.wav"), b"a")?;
+        fs::write(temp.path().join("b.mp3"), b"b")?;
+        let project = Project::from_dir(temp.path());
+        do_scan(&project, &Vec::new(), &scan_options(), true, false)?;
+
+        let conn = project.open_db_connection()?;
+        project.set_include_extensions(&conn, &[String::from("wav")])?;
+        do_scan(&project, &Vec::new(), &scan_options(), true, false)?;
+
+        let files = db::File::all(&conn, None)?;
+        assert_eq!(2, files.len());
+        assert!(files.iter().all(|x| !x.missing));
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_fails_on_database_error() -> Result<()> {
+        let temp = TempPath::new("scan-database-error");
+        fs::create_dir_all(temp.path())?;
+        fs::write(temp.path().join("a.wav"), b"a")?;
+        fs::write(temp.path().join("b.wav"), b"b")?;
+        let project = Project::from_dir(temp.path());
+        project.open_db_connection()?.execute_batch(
+            "CREATE TRIGGER fail_insert BEFORE INSERT ON files WHEN NEW.location = 'b.wav'
+             BEGIN SELECT RAISE(ABORT, 'Insert failed'); END",
+        )?;
+
+        let result = do_scan(&project, &Vec::new(), &scan_options(), true, false);
+
+        // Error is not reported as failure to process file and earlier insert is rolled back
+        assert!(matches!(result, Err(Error::Internal(..))));
+        assert!(db::File::all(&project.open_db_connection()?, None)?.is_empty());
+        Ok(())
+    }
+}
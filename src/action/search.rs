@@ -1,33 +1,36 @@
-use rusqlite::params;
-use rusqlite::types::Value;
-use std::rc::Rc;
-
+use crate::color::{colorize, is_terminal};
 use crate::db;
 use crate::location::Location;
 use crate::project::Project;
+use crate::query::Expr;
 use crate::result::Result;
-use crate::tag::Tag;
 
-pub fn do_search(project: &Project, tags: &Vec<Tag>) -> Result<()> {
+pub fn do_search(project: &Project, expr: &Expr) -> Result<()> {
     let conn = project.open_db_connection()?;
-    let names = tags.into_iter().map(|x| x.as_str()).collect();
-    let tags = db::Tag::all_by_names(&conn, &names)?;
 
-    let tag_ids = tags.iter().map(|x| x.id).collect::<Vec<_>>();
-    let tag_id_values = Rc::new(
-        tag_ids
-            .iter()
-            .map(|&x| Value::from(x))
-            .collect::<Vec<Value>>(),
-    );
-    let mut stmt =
-        conn.prepare("SELECT files.location FROM file_tags INNER JOIN files ON files.id = file_id WHERE tag_id IN RARRAY(?1)")?;
-    let locations = stmt
-        .query_map(params![tag_id_values], |row| row.get::<_, Location>(0))?
+    let (condition, values) = expr.to_sql();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT files.id, files.location FROM files WHERE {} ORDER BY files.location",
+        condition
+    ))?;
+    let files = stmt
+        .query_map(&values, |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, Location>(1)?))
+        })?
         .collect::<rusqlite::Result<Vec<_>>>()?;
 
-    for location in locations {
-        println!("{}", location.as_str())
+    // Only show tags when writing to terminal so that output can be piped to other tools
+    let show_tags = is_terminal();
+    for (file_id, location) in files {
+        if show_tags {
+            let tags = db::Tag::all_by_file_id(&conn, file_id)?
+                .iter()
+                .map(|x| colorize(&x.name, &x.color))
+                .collect::<Vec<_>>();
+            println!("{}  {}", location.as_str(), tags.join(" "))
+        } else {
+            println!("{}", location.as_str())
+        }
     }
 
     Ok(())
@@ -1,10 +1,13 @@
 use rusqlite::params;
 use std::path::Path;
 
+use crate::color::colorize;
 use crate::db::File;
 use crate::location::Location;
 use crate::project::Project;
 use crate::result::Result;
+use crate::signature::Scope;
+use crate::tag::TagValue;
 
 pub fn do_show_file(project: &Project, path: &impl AsRef<Path>) -> Result<()> {
     let conn = project.open_db_connection()?;
@@ -14,17 +17,51 @@ pub fn do_show_file(project: &Project, path: &impl AsRef<Path>) -> Result<()> {
 
     println!("Path: {}", path.as_ref().display());
     println!("Location: {}", file.location.as_str());
-    println!("Signature: {}", file.signature.as_str());
+    println!("Signature: {}", file.signature);
+    if let Some(stat) = file.stat {
+        println!("Size: {} bytes", stat.size);
+    }
+    if file.signature.scope() == Scope::Audio {
+        println!("Audio payload size: {} bytes", file.signature.size());
+    }
 
     println!("Tags:");
     let mut stmt =
-    conn.prepare("SELECT DISTINCT tags.name FROM file_tags INNER JOIN tags ON tags.id = file_tags.tag_id WHERE file_id = ?1 ORDER BY tags.name")?;
-    let tag_names = stmt
-        .query_map(params![file.id], |row| row.get::<_, String>(0))?
+    conn.prepare("SELECT tags.name, file_tags.value, tags.color, 0 FROM file_tags INNER JOIN tags ON tags.id = file_tags.tag_id WHERE file_tags.file_id = ?1 UNION SELECT tags.name, content_tags.value, tags.color, 1 FROM content_tags INNER JOIN tags ON tags.id = content_tags.tag_id WHERE content_tags.content_id = ?2 ORDER BY 1, 4")?;
+    let tags = stmt
+        .query_map(params![file.id, file.content_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<TagValue>>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, bool>(3)?,
+            ))
+        })?
         .collect::<rusqlite::Result<Vec<_>>>()?;
 
-    for tag_name in tag_names {
-        println!("  {}", tag_name);
+    for (tag_name, value, color, is_content) in tags {
+        let suffix = if is_content { " (content)" } else { "" };
+        match value {
+            Some(v) => println!(
+                "  {}={} ({}){}",
+                colorize(&tag_name, &color),
+                v,
+                v.type_name(),
+                suffix
+            ),
+            None => println!("  {}{}", colorize(&tag_name, &color), suffix),
+        }
+    }
+
+    let copies = File::all_by_signature(&conn, &file.signature)?
+        .into_iter()
+        .filter(|x| x.id != file.id)
+        .collect::<Vec<_>>();
+    if !copies.is_empty() {
+        println!("Copies:");
+        for copy in copies {
+            println!("  {}", copy.location.as_str());
+        }
     }
 
     Ok(())
@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+
+use crate::db;
+use crate::failure::{check_failures, show_failures, Failure};
+use crate::file_info::FileStat;
+use crate::fingerprint::Fingerprint;
+use crate::progress::Progress;
+use crate::project::Project;
+use crate::result::{user_error, Result};
+
+// Number of fingerprints stored per transaction so that interrupted runs lose little work
+const BATCH_SIZE: usize = 100;
+
+pub fn do_similar(project: &Project, threshold: f64) -> Result<()> {
+    let conn = project.open_db_connection()?;
+    let files = db::File::all(&conn, None)?
+        .into_iter()
+        .filter(|x| !x.missing)
+        .collect::<Vec<_>>();
+
+    // Fingerprint is computed once per content and only for files that have none yet
+    let mut fingerprints = files
+        .iter()
+        .filter_map(|x| x.fingerprint.clone().map(|f| (x.content_id, f)))
+        .collect::<HashMap<_, _>>();
+    let pending = files
+        .iter()
+        .filter(|x| x.fingerprint.is_none())
+        .collect::<Vec<_>>();
+    let total_bytes = pending
+        .iter()
+        .filter_map(|x| x.stat.map(|stat| stat.size))
+        .sum::<i64>();
+    let mut progress = Progress::new(pending.len() as u64, total_bytes as u64);
+
+    let mut batch = db::Batch::new(&conn, BATCH_SIZE)?;
+    let mut failures = Vec::new();
+    for file in pending {
+        let size = file.stat.map_or(0, |x| x.size as u64);
+        if let Some(fingerprint) = fingerprints.get(&file.content_id) {
+            db::File::update_fingerprint(batch.conn(), file.id, fingerprint)?;
+            progress.update(size, 0);
+            batch.tick()?;
+            continue;
+        }
+
+        // Fingerprint of file modified since last scan would not describe its content
+        let path = file.location.to_path(&project.dir);
+        if FileStat::from_path(&path).ok() != file.stat {
+            progress.update(size, 0);
+            failures.push(Failure::new(
+                &path,
+                user_error("File has changed since last scan"),
+            ));
+            continue;
+        }
+
+        let result = Fingerprint::from_file(&path);
+        progress.update(size, size);
+        match result {
+            Ok(Some(fingerprint)) => {
+                db::File::update_fingerprint(batch.conn(), file.id, &fingerprint)?;
+                fingerprints.insert(file.content_id, fingerprint);
+            }
+            Ok(None) => {
+                let fingerprint = Fingerprint::empty();
+                db::File::update_fingerprint(batch.conn(), file.id, &fingerprint)?;
+                fingerprints.insert(file.content_id, fingerprint);
+            }
+            Err(e) => failures.push(Failure::new(&path, e)),
+        }
+        batch.tick()?;
+    }
+    progress.finish();
+    batch.commit()?;
+
+    // Contents are compared in order of duration so that comparisons can stop as soon as
+    // durations differ too much for any later content to be similar
+    let mut contents = fingerprints
+        .into_iter()
+        .filter(|(_, x)| !x.is_empty())
+        .collect::<Vec<_>>();
+    contents.sort_by_key(|(content_id, fingerprint)| (fingerprint.duration_ms(), *content_id));
+    let mut clusters = Clusters::new(contents.len());
+    for i in 0..contents.len() {
+        for j in i + 1..contents.len() {
+            let (a, b) = (&contents[i].1, &contents[j].1);
+            if a.duration_ratio(b) < threshold {
+                break;
+            }
+            let similarity = a.similarity(b);
+            if similarity >= threshold {
+                clusters.join(i, j, similarity);
+            }
+        }
+    }
+
+    let mut cluster_count = 0;
+    for (members, similarity) in clusters.all() {
+        cluster_count += 1;
+        println!("Similarity: {:.3}", similarity);
+        for index in members {
+            let content_id = contents[index].0;
+            for file in files.iter().filter(|x| x.content_id == content_id) {
+                println!("  {}", file.location.as_str());
+            }
+        }
+    }
+
+    show_failures(&failures);
+    println!("Found {} clusters of similar files", cluster_count);
+    check_failures(&failures)
+}
+
+// Disjoint sets of contents joined by pairwise similarity, tracking lowest similarity of
+// any pair that joined each set
+struct Clusters {
+    parents: Vec<usize>,
+    similarities: Vec<Option<f64>>,
+}
+
+impl Clusters {
+    fn new(count: usize) -> Self {
+        Self {
+            parents: (0..count).collect(),
+            similarities: vec![None; count],
+        }
+    }
+
+    fn root(&mut self, index: usize) -> usize {
+        let parent = self.parents[index];
+        if parent == index {
+            return index;
+        }
+        let root = self.root(parent);
+        self.parents[index] = root;
+        root
+    }
+
+    fn join(&mut self, a: usize, b: usize, similarity: f64) {
+        let (a, b) = (self.root(a), self.root(b));
+        let lowest = [self.similarities[a], self.similarities[b], Some(similarity)]
+            .iter()
+            .filter_map(|x| *x)
+            .fold(similarity, f64::min);
+        self.parents[b] = a;
+        self.similarities[a] = Some(lowest);
+    }
+
+    // Sets with more than one member along with lowest similarity within each
+    fn all(mut self) -> Vec<(Vec<usize>, f64)> {
+        let mut members = HashMap::<usize, Vec<usize>>::new();
+        for index in 0..self.parents.len() {
+            let root = self.root(index);
+            members.entry(root).or_default().push(index);
+        }
+        let mut result = members
+            .into_iter()
+            .filter_map(|(root, x)| self.similarities[root].map(|similarity| (x, similarity)))
+            .collect::<Vec<_>>();
+        result.sort_by_key(|(x, _)| x[0]);
+        result
+    }
+}
@@ -1,54 +1,92 @@
-use std::collections::HashMap;
+use rusqlite::Connection;
+use std::collections::HashSet;
 use std::fmt::Debug;
 use std::path::Path;
 
+use super::change_set::{Change, ChangeSet};
+use super::util::get_files_by_paths;
 use crate::db;
-use crate::location::Location;
 use crate::project::Project;
-use crate::result::{user_error_result, Result};
+use crate::result::Result;
 use crate::tag::Tag;
 
 pub fn do_tag(
     project: &Project,
     tags: &Vec<Tag>,
     paths: &Vec<impl AsRef<Path> + Debug>,
+    content: bool,
+    dry_run: bool,
 ) -> Result<()> {
-    let conn = project.open_db_connection()?;
-
-    let locations = paths
-        .into_iter()
-        .map(|x| Location::from_path(&project.dir, x))
-        .collect::<Result<_>>()?;
-    let files = db::File::all_by_locations(&conn, &locations)?;
-    if files.len() != locations.len() {
-        let h = files
-            .iter()
-            .map(|x| (&x.location, x))
-            .collect::<HashMap<_, _>>();
-        let missing_locations_str = locations
-            .iter()
-            .filter(|&x| !h.contains_key(&x))
-            .map(|x| x.as_str())
-            .collect::<Vec<_>>()
-            .join(", ");
-        return user_error_result(format!(
-            "No files found at locations {}",
-            missing_locations_str
-        ));
-    }
+    let mut conn = project.open_db_connection()?;
+    let tx = conn.transaction()?;
 
-    for tag in tags {
-        let _ = db::Tag::upsert(&conn, tag)?;
+    let mut files = get_files_by_paths(&tx, project, paths)?;
+    if content {
+        // Tag each content once however many of its copies are given
+        let mut content_ids = HashSet::new();
+        files.retain(|x| content_ids.insert(x.content_id));
     }
 
     let names = tags.into_iter().map(|x| x.as_str()).collect();
-    let tags = db::Tag::all_by_names(&conn, &names)?;
+    let names = db::TagAlias::resolve_names(&tx, &names)?;
 
-    for file in &files {
-        for tag in &tags {
-            let _ = db::FileTag::upsert(&conn, file.id, tag.id)?;
+    let mut changes = ChangeSet::new(dry_run);
+    for (tag, name) in tags.iter().zip(&names) {
+        let tag = Tag::new(name, tag.value().cloned());
+        if changes.is_enabled() {
+            collect_new_tags(&tx, &tag, &mut changes)?;
+        }
+        let tag_id = db::Tag::upsert(&tx, &tag)?;
+        for file in &files {
+            if content {
+                if changes.is_enabled() {
+                    let existing = db::ContentTag::by_ids(&tx, file.content_id, tag_id)?;
+                    if existing.map_or(true, |x| x.value.as_ref() != tag.value()) {
+                        changes.push(Change::AddContentTag(
+                            format_tag(&tag),
+                            String::from(file.location.as_str()),
+                        ));
+                    }
+                }
+                let _ = db::ContentTag::upsert(&tx, file.content_id, tag_id, tag.value())?;
+            } else {
+                if changes.is_enabled() {
+                    let existing = db::FileTag::by_ids(&tx, file.id, tag_id)?;
+                    if existing.map_or(true, |x| x.value.as_ref() != tag.value()) {
+                        changes.push(Change::AddTag(
+                            format_tag(&tag),
+                            String::from(file.location.as_str()),
+                        ));
+                    }
+                }
+                let _ = db::FileTag::upsert(&tx, file.id, tag_id, tag.value())?;
+            }
         }
     }
 
+    if dry_run {
+        changes.show();
+        tx.rollback()?;
+    } else {
+        tx.commit()?;
+    }
     Ok(())
 }
+
+// Records tag and any of its ancestors that do not exist yet, outermost first
+fn collect_new_tags(conn: &Connection, tag: &Tag, changes: &mut ChangeSet) -> Result<()> {
+    if db::Tag::by_name(conn, tag.as_str())?.is_none() {
+        if let Some(parent) = tag.parent() {
+            collect_new_tags(conn, &parent, changes)?;
+        }
+        changes.push(Change::InsertTag(String::from(tag.as_str())));
+    }
+    Ok(())
+}
+
+fn format_tag(tag: &Tag) -> String {
+    match tag.value() {
+        Some(value) => format!("{}{}{}", tag.as_str(), Tag::VALUE_SEPARATOR, value),
+        None => String::from(tag.as_str()),
+    }
+}
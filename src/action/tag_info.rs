@@ -0,0 +1,61 @@
+use crate::color::{colorize, parse_color};
+use crate::db;
+use crate::project::Project;
+use crate::result::{user_error_result, Result};
+use crate::tag::Tag;
+
+pub struct TagInfoUpdate<'a> {
+    pub description: Option<&'a str>,
+    pub color: Option<&'a str>,
+    pub category: Option<&'a str>,
+}
+
+pub fn do_tag_info(project: &Project, tag: &Tag, update: &TagInfoUpdate) -> Result<()> {
+    let conn = project.open_db_connection()?;
+
+    let names = db::TagAlias::resolve_names(&conn, &vec![tag.as_str()])?;
+    let mut tag = match db::Tag::by_name(&conn, &names[0])? {
+        Some(x) => x,
+        None => return user_error_result(format!("No tag found with name {}", tag.as_str())),
+    };
+
+    if let Some(c) = update.color {
+        if !c.is_empty() {
+            let _ = parse_color(c)?;
+        }
+    }
+
+    if update.description.is_some() || update.color.is_some() || update.category.is_some() {
+        // Empty string clears a field
+        let to_value = |s: &str| match s {
+            "" => None,
+            _ => Some(String::from(s)),
+        };
+        if let Some(s) = update.description {
+            tag.description = to_value(s)
+        }
+        if let Some(s) = update.color {
+            tag.color = to_value(s)
+        }
+        if let Some(s) = update.category {
+            tag.category = to_value(s)
+        }
+        db::Tag::update_info(&conn, &tag)?;
+    }
+
+    println!("Tag: {}", colorize(&tag.name, &tag.color));
+    println!(
+        "Description: {}",
+        tag.description.as_ref().map_or("(none)", |x| x.as_str())
+    );
+    println!(
+        "Color: {}",
+        tag.color.as_ref().map_or("(none)", |x| x.as_str())
+    );
+    println!(
+        "Category: {}",
+        tag.category.as_ref().map_or("(none)", |x| x.as_str())
+    );
+
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use itertools::Itertools;
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::path::Path;
+
+use super::util::get_files_by_paths;
+use crate::db;
+use crate::project::Project;
+use crate::result::{user_error_result, Result};
+use crate::tag::Tag;
+
+pub fn do_untag(
+    project: &Project,
+    tags: &Vec<Tag>,
+    paths: &Vec<impl AsRef<Path> + Debug>,
+    content: bool,
+    delete_unused_tags: bool,
+) -> Result<()> {
+    let mut conn = project.open_db_connection()?;
+    let tx = conn.transaction()?;
+
+    let files = get_files_by_paths(&tx, project, paths)?;
+
+    let names = tags.into_iter().map(|x| x.as_str()).collect();
+    let names = db::TagAlias::resolve_names(&tx, &names)?;
+    let names = names
+        .iter()
+        .map(|x| x.as_str())
+        .unique()
+        .collect::<Vec<_>>();
+    let tags = db::Tag::all_by_names(&tx, &names)?;
+    if tags.len() != names.len() {
+        let h = tags.iter().map(|x| x.name.as_str()).collect::<HashSet<_>>();
+        let missing_names_str = names
+            .iter()
+            .filter(|&x| !h.contains(x))
+            .copied()
+            .collect::<Vec<_>>()
+            .join(", ");
+        return user_error_result(format!("No tags found with names {}", missing_names_str));
+    }
+
+    for file in &files {
+        for tag in &tags {
+            if content {
+                if db::ContentTag::delete(&tx, file.content_id, tag.id)? != 0 {
+                    println!(
+                        "Removed tag {} from content of {}",
+                        tag.name,
+                        file.location.as_str()
+                    )
+                }
+            } else if db::FileTag::delete(&tx, file.id, tag.id)? != 0 {
+                println!("Removed tag {} from {}", tag.name, file.location.as_str())
+            }
+        }
+    }
+
+    for tag in &tags {
+        if db::FileTag::count_by_tag_id(&tx, tag.id)?
+            + db::ContentTag::count_by_tag_id(&tx, tag.id)?
+            == 0
+        {
+            if delete_unused_tags && !db::Tag::children(&tx, tag.id)?.is_empty() {
+                println!(
+                    "Tag {} is no longer applied to any files but has child tags",
+                    tag.name
+                )
+            } else if delete_unused_tags {
+                db::Tag::delete_by_id(&tx, tag.id)?;
+                println!(
+                    "Deleted tag {} which is no longer applied to any files",
+                    tag.name
+                )
+            } else {
+                println!("Tag {} is no longer applied to any files", tag.name)
+            }
+        }
+    }
+
+    tx.commit()?;
+    Ok(())
+}
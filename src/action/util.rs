@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use rusqlite::Connection;
+
+use crate::db;
+use crate::location::Location;
+use crate::project::Project;
+use crate::result::{user_error_result, Result};
+
+// Resolves paths, defaulting to whole project, and drops any path that lies within
+// another one
+pub fn get_locations_within_project(
+    project: &Project,
+    paths: &Vec<impl AsRef<Path>>,
+) -> Result<Vec<Location>> {
+    if paths.is_empty() {
+        return Ok(vec![Location::from_path(&project.dir, &project.dir)?]);
+    }
+
+    let mut locations = Vec::new();
+    for path in paths {
+        let path = path.as_ref();
+        if !path.starts_with(&project.dir) {
+            return user_error_result(format!(
+                "Path {} is not inside project directory {}",
+                path.display(),
+                project.dir.display()
+            ));
+        }
+        locations.push(Location::from_path(&project.dir, path)?);
+    }
+    locations.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+
+    let mut result: Vec<Location> = Vec::new();
+    for location in locations {
+        if !result.iter().any(|x| x.contains(&location)) {
+            result.push(location);
+        }
+    }
+    Ok(result)
+}
+
+pub fn get_files_by_paths(
+    conn: &Connection,
+    project: &Project,
+    paths: &Vec<impl AsRef<Path>>,
+) -> Result<Vec<db::File>> {
+    let locations = paths
+        .into_iter()
+        .map(|x| Location::from_path(&project.dir, x))
+        .collect::<Result<_>>()?;
+    let files = db::File::all_by_locations(&conn, &locations)?;
+    if files.len() != locations.len() {
+        let h = files
+            .iter()
+            .map(|x| (&x.location, x))
+            .collect::<HashMap<_, _>>();
+        let missing_locations_str = locations
+            .iter()
+            .filter(|&x| !h.contains_key(&x))
+            .map(|x| x.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        return user_error_result(format!(
+            "No files found at locations {}",
+            missing_locations_str
+        ));
+    }
+    Ok(files)
+}
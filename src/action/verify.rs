@@ -0,0 +1,122 @@
+use crate::db;
+use crate::failure::{check_failures, show_failures, Failure};
+use crate::file_info::FileStat;
+use crate::progress::Progress;
+use crate::project::Project;
+use crate::result::{user_error, user_error_result, Result};
+use crate::signature::Signature;
+use crate::util::{unix_time_now, SECONDS_PER_DAY};
+
+// Number of files verified per transaction so that interrupted verification loses
+// little work
+const BATCH_SIZE: usize = 100;
+
+// Start time of verification that has not completed yet
+const VERIFY_STARTED_SETTING: &str = "verify_started";
+
+pub fn do_verify(project: &Project, days: Option<i64>, resume: bool) -> Result<()> {
+    let conn = project.open_db_connection()?;
+    let now = unix_time_now()?;
+
+    let started = if resume {
+        match db::Setting::by_name(&conn, VERIFY_STARTED_SETTING)? {
+            Some(x) => match x.value.parse::<i64>() {
+                Ok(started) => started,
+                Err(_) => return user_error_result("Interrupted verification is invalid"),
+            },
+            None => return user_error_result("No interrupted verification to resume"),
+        }
+    } else {
+        db::Setting::upsert(&conn, VERIFY_STARTED_SETTING, &now.to_string())?;
+        now
+    };
+
+    // Skip files already verified by this verification or within given number of days
+    let verified_before = match days {
+        Some(x) => started.min(now - x * SECONDS_PER_DAY),
+        None => started,
+    };
+    let files = db::File::all_unverified(&conn, verified_before)?;
+    let total_bytes = files
+        .iter()
+        .filter_map(|x| x.stat.map(|stat| stat.size))
+        .sum::<i64>();
+    let mut progress = Progress::new(files.len() as u64, total_bytes as u64);
+
+    let mut batch = db::Batch::new(&conn, BATCH_SIZE)?;
+    let mut verified = 0;
+    let mut failures = Vec::new();
+    for file in &files {
+        let path = file.location.to_path(&project.dir);
+        let size = file.stat.map_or(0, |x| x.size as u64);
+        // Files signed before their format was signed by audio payload are still signed
+        // whole
+        let result = Signature::from_file_with_scope(
+            &path,
+            file.signature.algorithm(),
+            file.signature.scope(),
+        );
+        progress.update(size, size);
+        match result {
+            Ok(signature) if signature == file.signature => {
+                db::File::mark_verified(batch.conn(), file.id, unix_time_now()?)?;
+                verified += 1;
+            }
+            // A file modified by another application has a different size or modification
+            // time while a corrupted file does not
+            Ok(_) if FileStat::from_path(&path).ok() != file.stat => failures.push(Failure::new(
+                &path,
+                user_error("File has changed since last scan"),
+            )),
+            Ok(_) => failures.push(Failure::new(
+                &path,
+                user_error("Signature does not match database: file may be corrupted"),
+            )),
+            Err(e) => failures.push(Failure::new(&path, e)),
+        }
+        batch.tick()?;
+    }
+    progress.finish();
+
+    db::Setting::delete_by_name(batch.conn(), VERIFY_STARTED_SETTING)?;
+    batch.commit()?;
+
+    show_failures(&failures);
+    println!("Verified {} of {} files", verified, files.len());
+    check_failures(&failures)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use std::fs;
+
+    use super::*;
+    use crate::file_info::FileInfo;
+    use crate::location::Location;
+    use crate::signature::{Algorithm, Scope};
+    use crate::test_util::TempPath;
+
+    #[test]
+    fn test_verify_whole_file_signed_audio() -> Result<()> {
+        let temp = TempPath::new("verify-whole-file");
+        fs::create_dir_all(temp.path())?;
+        let path = temp.path().join("sample.wav");
+        fs::write(&path, b"RIFF\x10\0\0\0WAVEdata\x03\0\0\0abc\0")?;
+        let project = Project::from_dir(temp.path());
+        let conn = project.open_db_connection()?;
+        let mut file_info = FileInfo::new(
+            Location::try_from("sample.wav")?,
+            Signature::from_file_with_scope(&path, Algorithm::Sha256, Scope::File)?,
+        );
+        file_info.stat = Some(FileStat::from_path(&path)?);
+        let id = db::File::insert(&conn, &file_info)?;
+
+        do_verify(&project, None, false)?;
+
+        assert!(db::File::all(&conn, None)?
+            .iter()
+            .any(|x| x.id == id && x.last_verified.is_some()));
+        Ok(())
+    }
+}
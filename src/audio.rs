@@ -0,0 +1,441 @@
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::Path;
+
+use crate::result::Result;
+
+// Size of ID3v1 tag appended to end of MP3 files
+const ID3V1_SIZE: u64 = 128;
+
+// Size of ID3v2 header and of optional ID3v2 footer
+const ID3V2_HEADER_SIZE: u64 = 10;
+
+// Returns byte range of audio payload so that metadata edited by other applications can
+// be excluded from signature, or None if format is not recognized or is malformed: MP3
+// files have no file header so their frames are only looked for if caller says so
+pub fn payload_range<R: Read + Seek>(r: &mut R, mp3: bool) -> Result<Option<Range<u64>>> {
+    let len = r.seek(SeekFrom::End(0))?;
+    r.seek(SeekFrom::Start(0))?;
+
+    let mut header = [0u8; 12];
+    if !read_exact_or_eof(r, &mut header)? {
+        return Ok(None);
+    }
+
+    let range = match &header[0..4] {
+        b"RIFF" if &header[8..12] == b"WAVE" => find_chunk(r, len, b"data", u32::from_le_bytes)?,
+        b"FORM" if &header[8..12] == b"AIFF" || &header[8..12] == b"AIFC" => {
+            find_chunk(r, len, b"SSND", u32::from_be_bytes)?
+        }
+        _ if mp3 => mp3_frames(r, len, &header)?,
+        _ => None,
+    };
+    Ok(range.filter(|x| x.start < x.end && x.end <= len))
+}
+
+// Other files such as MIDI data may start with bytes resembling MPEG frame sync
+pub fn is_mp3(path: &Path) -> bool {
+    match path.extension().and_then(|x| x.to_str()) {
+        Some(x) => x.eq_ignore_ascii_case("mp3"),
+        None => false,
+    }
+}
+
+// Layout of uncompressed samples in WAV or AIFF file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PcmFormat {
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub bits: u16,
+    pub float: bool,
+    pub big_endian: bool,
+}
+
+impl PcmFormat {
+    pub fn sample_size(&self) -> usize {
+        (self.bits / 8) as usize
+    }
+
+    pub fn frame_size(&self) -> usize {
+        self.sample_size() * self.channels as usize
+    }
+
+    // Decodes single sample to value between -1 and 1 regardless of bit depth
+    pub fn decode_sample(&self, bytes: &[u8]) -> f32 {
+        if self.float {
+            let b = [bytes[0], bytes[1], bytes[2], bytes[3]];
+            return if self.big_endian {
+                f32::from_be_bytes(b)
+            } else {
+                f32::from_le_bytes(b)
+            };
+        }
+
+        // Sample is shifted into most significant bits so that all bit depths share scale
+        let n = bytes.len();
+        let mut value = 0u32;
+        for i in 0..n {
+            let byte = if self.big_endian {
+                bytes[i]
+            } else {
+                bytes[n - 1 - i]
+            };
+            value = (value << 8) | byte as u32;
+        }
+        value <<= 32 - 8 * n as u32;
+
+        // 8-bit WAV samples are unsigned
+        if n == 1 && !self.big_endian {
+            value ^= 0x8000_0000;
+        }
+        value as i32 as f32 / 2147483648f32
+    }
+}
+
+// Returns format and byte range of samples of uncompressed WAV or AIFF file, or None if
+// file is in another format or uses unsupported encoding
+pub fn pcm_range<R: Read + Seek>(r: &mut R) -> Result<Option<(PcmFormat, Range<u64>)>> {
+    let len = r.seek(SeekFrom::End(0))?;
+    r.seek(SeekFrom::Start(0))?;
+
+    let mut header = [0u8; 12];
+    if !read_exact_or_eof(r, &mut header)? {
+        return Ok(None);
+    }
+
+    let result = match (&header[0..4], &header[8..12]) {
+        (b"RIFF", b"WAVE") => {
+            let format = match find_chunk(r, len, b"fmt ", u32::from_le_bytes)? {
+                Some(x) => read_chunk(r, x)?.and_then(|x| wav_format(&x)),
+                None => None,
+            };
+            match format {
+                Some(format) => {
+                    find_chunk(r, len, b"data", u32::from_le_bytes)?.map(|range| (format, range))
+                }
+                None => None,
+            }
+        }
+        (b"FORM", form @ b"AIFF") | (b"FORM", form @ b"AIFC") => {
+            let format = match find_chunk(r, len, b"COMM", u32::from_be_bytes)? {
+                Some(x) => read_chunk(r, x)?.and_then(|x| aiff_format(&x, form == b"AIFC")),
+                None => None,
+            };
+            match (format, find_chunk(r, len, b"SSND", u32::from_be_bytes)?) {
+                (Some(format), Some(range)) if range.end - range.start >= 8 => {
+                    // Sound data is preceded by offset and block size
+                    r.seek(SeekFrom::Start(range.start))?;
+                    let mut offset = [0u8; 4];
+                    r.read_exact(&mut offset)?;
+                    let start = range.start + 8 + u32::from_be_bytes(offset) as u64;
+                    Some((format, start..range.end))
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+    Ok(result.filter(|(_, x)| x.start <= x.end && x.end <= len))
+}
+
+fn wav_format(data: &[u8]) -> Option<PcmFormat> {
+    if data.len() < 16 {
+        return None;
+    }
+    let u16_at = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]);
+    let mut tag = u16_at(0);
+    // Extensible format stores actual format tag at start of subformat GUID
+    if tag == 0xfffe && data.len() >= 26 {
+        tag = u16_at(24);
+    }
+    let format = PcmFormat {
+        channels: u16_at(2),
+        sample_rate: u32::from_le_bytes([data[4], data[5], data[6], data[7]]),
+        bits: u16_at(14),
+        float: tag == 3,
+        big_endian: false,
+    };
+    match (tag, format.bits) {
+        (1, 8) | (1, 16) | (1, 24) | (1, 32) | (3, 32) if format.channels > 0 => Some(format),
+        _ => None,
+    }
+}
+
+fn aiff_format(data: &[u8], compressed: bool) -> Option<PcmFormat> {
+    if data.len() < 18 || compressed && data.len() < 22 {
+        return None;
+    }
+    let (float, big_endian) = if compressed {
+        match &data[18..22] {
+            b"NONE" | b"twos" => (false, true),
+            b"sowt" => (false, false),
+            b"fl32" | b"FL32" => (true, true),
+            _ => return None,
+        }
+    } else {
+        (false, true)
+    };
+    let format = PcmFormat {
+        channels: u16::from_be_bytes([data[0], data[1]]),
+        sample_rate: extended_to_u32(&data[8..18]),
+        bits: u16::from_be_bytes([data[6], data[7]]),
+        float: float,
+        big_endian: big_endian,
+    };
+    match (float, format.bits) {
+        (false, 8) | (false, 16) | (false, 24) | (false, 32) | (true, 32)
+            if format.channels > 0 =>
+        {
+            Some(format)
+        }
+        _ => None,
+    }
+}
+
+// Converts 80-bit IEEE 754 extended precision value used for AIFF sample rate
+fn extended_to_u32(bytes: &[u8]) -> u32 {
+    let exponent = (((bytes[0] & 0x7f) as i32) << 8 | bytes[1] as i32) - 16383;
+    let mut mantissa = 0u64;
+    for byte in &bytes[2..10] {
+        mantissa = (mantissa << 8) | *byte as u64;
+    }
+    (mantissa as f64 * 2f64.powi(exponent - 63)).round() as u32
+}
+
+// Reads contents of small chunk such as format description
+fn read_chunk<R: Read + Seek>(r: &mut R, range: Range<u64>) -> Result<Option<Vec<u8>>> {
+    r.seek(SeekFrom::Start(range.start))?;
+    let mut data = vec![0u8; (range.end - range.start).min(1024) as usize];
+    Ok(if read_exact_or_eof(r, &mut data)? {
+        Some(data)
+    } else {
+        None
+    })
+}
+
+// Walks RIFF or IFF chunks following 12-byte file header looking for chunk with given ID
+fn find_chunk<R: Read + Seek>(
+    r: &mut R,
+    len: u64,
+    id: &[u8; 4],
+    decode_size: fn([u8; 4]) -> u32,
+) -> Result<Option<Range<u64>>> {
+    let mut offset = 12;
+    while offset + 8 <= len {
+        r.seek(SeekFrom::Start(offset))?;
+        let mut chunk_header = [0u8; 8];
+        if !read_exact_or_eof(r, &mut chunk_header)? {
+            return Ok(None);
+        }
+        let size = decode_size([
+            chunk_header[4],
+            chunk_header[5],
+            chunk_header[6],
+            chunk_header[7],
+        ]) as u64;
+        let start = offset + 8;
+        if &chunk_header[0..4] == id {
+            return Ok(Some(start..start + size));
+        }
+        // Chunks are padded to an even number of bytes
+        offset = start + size + size % 2;
+    }
+    Ok(None)
+}
+
+// Skips leading ID3v2 tag and trailing ID3v1 tag of file starting with either an ID3v2
+// tag or an MPEG audio frame sync
+fn mp3_frames<R: Read + Seek>(
+    r: &mut R,
+    len: u64,
+    header: &[u8; 12],
+) -> Result<Option<Range<u64>>> {
+    let start = if &header[0..3] == b"ID3" {
+        // Tag size is stored as four 7-bit bytes and excludes header and footer
+        let size = header[6..10]
+            .iter()
+            .fold(0u64, |acc, x| (acc << 7) | (*x & 0x7f) as u64);
+        let footer = if header[5] & 0x10 != 0 {
+            ID3V2_HEADER_SIZE
+        } else {
+            0
+        };
+        ID3V2_HEADER_SIZE + size + footer
+    } else if header[0] == 0xff && header[1] & 0xe0 == 0xe0 {
+        0
+    } else {
+        return Ok(None);
+    };
+
+    let mut end = len;
+    if len >= start + ID3V1_SIZE {
+        r.seek(SeekFrom::Start(len - ID3V1_SIZE))?;
+        let mut tag = [0u8; 3];
+        if read_exact_or_eof(r, &mut tag)? && &tag == b"TAG" {
+            end = len - ID3V1_SIZE;
+        }
+    }
+    Ok(Some(start..end))
+}
+
+fn read_exact_or_eof<R: Read>(r: &mut R, buf: &mut [u8]) -> Result<bool> {
+    match r.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn chunk(id: &[u8; 4], data: &[u8], encode_size: fn(u32) -> [u8; 4]) -> Vec<u8> {
+        let mut result = id.to_vec();
+        result.extend(&encode_size(data.len() as u32));
+        result.extend(data);
+        if data.len() % 2 == 1 {
+            result.push(0);
+        }
+        result
+    }
+
+    fn range(bytes: Vec<u8>) -> Result<Option<Range<u64>>> {
+        payload_range(&mut Cursor::new(bytes), false)
+    }
+
+    fn mp3_range(bytes: Vec<u8>) -> Result<Option<Range<u64>>> {
+        payload_range(&mut Cursor::new(bytes), true)
+    }
+
+    #[test]
+    fn test_wav() -> Result<()> {
+        let mut bytes = b"RIFF\0\0\0\0WAVE".to_vec();
+        bytes.extend(chunk(b"fmt ", &[1; 16], u32::to_le_bytes));
+        bytes.extend(chunk(b"LIST", b"odd", u32::to_le_bytes));
+        let start = bytes.len() as u64 + 8;
+        bytes.extend(chunk(b"data", &[2; 6], u32::to_le_bytes));
+        bytes.extend(chunk(b"bext", &[3; 10], u32::to_le_bytes));
+        assert_eq!(Some(start..start + 6), range(bytes)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_aiff() -> Result<()> {
+        let mut bytes = b"FORM\0\0\0\0AIFF".to_vec();
+        bytes.extend(chunk(b"COMM", &[1; 18], u32::to_be_bytes));
+        let start = bytes.len() as u64 + 8;
+        bytes.extend(chunk(b"SSND", &[2; 12], u32::to_be_bytes));
+        assert_eq!(Some(start..start + 12), range(bytes)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_mp3() -> Result<()> {
+        let frames = [0xff, 0xfb, 0x90, 0x64, 0, 0, 0, 0, 0, 0, 0, 0];
+
+        assert_eq!(Some(0..12), mp3_range(frames.to_vec())?);
+        assert_eq!(None, range(frames.to_vec())?);
+
+        let mut bytes = b"ID3\x04\0\0\0\0\x01\x01".to_vec();
+        bytes.extend(&[0; 129]);
+        bytes.extend(&frames);
+        bytes.extend(b"TAG");
+        bytes.extend(&[0; 125]);
+        assert_eq!(Some(139..151), mp3_range(bytes)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_mp3() {
+        assert!(is_mp3(Path::new("p/q/r.mp3")));
+        assert!(is_mp3(Path::new("p/q/r.MP3")));
+        assert!(!is_mp3(Path::new("p/q/r.mid")));
+        assert!(!is_mp3(Path::new("p/q/mp3")));
+    }
+
+    #[test]
+    fn test_pcm_range_wav() -> Result<()> {
+        let mut fmt = Vec::new();
+        fmt.extend(&1u16.to_le_bytes());
+        fmt.extend(&2u16.to_le_bytes());
+        fmt.extend(&44100u32.to_le_bytes());
+        fmt.extend(&(44100u32 * 6).to_le_bytes());
+        fmt.extend(&6u16.to_le_bytes());
+        fmt.extend(&24u16.to_le_bytes());
+        let mut bytes = b"RIFF\0\0\0\0WAVE".to_vec();
+        bytes.extend(chunk(b"fmt ", &fmt, u32::to_le_bytes));
+        let start = bytes.len() as u64 + 8;
+        bytes.extend(chunk(b"data", &[0; 12], u32::to_le_bytes));
+
+        let (format, range) = pcm_range(&mut Cursor::new(bytes))?.unwrap();
+        assert_eq!(2, format.channels);
+        assert_eq!(44100, format.sample_rate);
+        assert_eq!(6, format.frame_size());
+        assert!(!format.float && !format.big_endian);
+        assert_eq!(start..start + 12, range);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pcm_range_aiff() -> Result<()> {
+        let mut comm = Vec::new();
+        comm.extend(&1u16.to_be_bytes());
+        comm.extend(&4u32.to_be_bytes());
+        comm.extend(&16u16.to_be_bytes());
+        // 48000 as 80-bit extended precision value
+        comm.extend(&[0x40, 0x0e, 0xbb, 0x80, 0, 0, 0, 0, 0, 0]);
+        let mut ssnd = vec![0, 0, 0, 2, 0, 0, 0, 0];
+        ssnd.extend(&[0; 10]);
+        let mut bytes = b"FORM\0\0\0\0AIFF".to_vec();
+        bytes.extend(chunk(b"COMM", &comm, u32::to_be_bytes));
+        let start = bytes.len() as u64 + 8;
+        bytes.extend(chunk(b"SSND", &ssnd, u32::to_be_bytes));
+
+        let (format, range) = pcm_range(&mut Cursor::new(bytes))?.unwrap();
+        assert_eq!(1, format.channels);
+        assert_eq!(48000, format.sample_rate);
+        assert_eq!(16, format.bits);
+        assert!(format.big_endian);
+        assert_eq!(start + 10..start + 18, range);
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_sample() {
+        let format = |bits, big_endian| PcmFormat {
+            channels: 1,
+            sample_rate: 44100,
+            bits: bits,
+            float: false,
+            big_endian: big_endian,
+        };
+        assert_eq!(0f32, format(8, false).decode_sample(&[0x80]));
+        assert_eq!(-1f32, format(8, false).decode_sample(&[0x00]));
+        assert_eq!(-1f32, format(8, true).decode_sample(&[0x80]));
+        assert_eq!(0.5f32, format(16, false).decode_sample(&[0x00, 0x40]));
+        assert_eq!(0.5f32, format(16, true).decode_sample(&[0x40, 0x00]));
+        assert_eq!(
+            -0.5f32,
+            format(24, false).decode_sample(&[0x00, 0x00, 0xc0])
+        );
+        assert_eq!(
+            format(16, false).decode_sample(&[0x34, 0x12]),
+            format(24, false).decode_sample(&[0x00, 0x34, 0x12])
+        );
+    }
+
+    #[test]
+    fn test_unrecognized() -> Result<()> {
+        assert_eq!(None, range(Vec::new())?);
+        assert_eq!(None, range(b"MThd\0\0\0\x06\0\x01\0\x01".to_vec())?);
+
+        // Data chunk extends past end of file
+        let mut bytes = b"RIFF\0\0\0\0WAVE".to_vec();
+        bytes.extend(b"data\xff\0\0\0");
+        assert_eq!(None, range(bytes)?);
+        Ok(())
+    }
+}
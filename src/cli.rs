@@ -1,5 +1,7 @@
 use clap::{crate_authors, App, AppSettings, Arg, SubCommand};
 
+use crate::signature::Algorithm;
+
 pub mod command {
     pub const CHECK_DATABASE: &str = "checkdb";
     pub const CHECK_FILE_SYSTEM: &str = "checkfs";
@@ -8,11 +10,23 @@ pub mod command {
     pub const SCAN: &str = "scan";
     pub const SEARCH: &str = "search";
     pub const TAG: &str = "tag";
+    pub const UNTAG: &str = "untag";
 
     // New commands
     pub const SHOW_FILE: &str = "showfile";
     pub const LIST_FILES: &str = "listfiles";
     pub const LIST_TAGS: &str = "listtags";
+    pub const MERGE_TAGS: &str = "merge-tags";
+    pub const RENAME_TAG: &str = "rename-tag";
+    pub const ADD_ALIAS: &str = "add-alias";
+    pub const REMOVE_ALIAS: &str = "remove-alias";
+    pub const LIST_ALIASES: &str = "list-aliases";
+    pub const TAG_INFO: &str = "tag-info";
+    pub const PRUNE: &str = "prune";
+    pub const REHASH: &str = "rehash";
+    pub const VERIFY: &str = "verify";
+    pub const SIMILAR: &str = "similar";
+    pub const FILTERS: &str = "filters";
 }
 
 pub mod arg {
@@ -23,11 +37,36 @@ pub mod arg {
     // New args
     pub const LIKE: &str = "like";
     pub const PATH: &str = "path";
+    pub const QUERY: &str = "query";
+    pub const DELETE_UNUSED_TAGS: &str = "delete-unused-tags";
+    pub const NEW_TAG: &str = "new-tag";
+    pub const OLD_TAG: &str = "old-tag";
+    pub const TAGS: &str = "tags";
+    pub const TREE: &str = "tree";
+    pub const ALIAS: &str = "alias";
+    pub const ALIASES: &str = "aliases";
+    pub const CATEGORY: &str = "category";
+    pub const COLOR: &str = "color";
+    pub const DESCRIPTION: &str = "description";
+    pub const FULL: &str = "full";
+    pub const JOBS: &str = "jobs";
+    pub const OLDER_THAN: &str = "older-than";
+    pub const JSON: &str = "json";
+    pub const DRY_RUN: &str = "dry-run";
+    pub const CONTENT: &str = "content";
+    pub const ALGORITHM: &str = "algorithm";
+    pub const DAYS: &str = "days";
+    pub const RESUME: &str = "resume";
+    pub const THRESHOLD: &str = "threshold";
+    pub const INCLUDE: &str = "include";
+    pub const EXCLUDE: &str = "exclude";
+    pub const MIN_SIZE: &str = "min-size";
+    pub const MAX_SIZE: &str = "max-size";
 }
 
 pub fn make_app<'a, 'b>() -> App<'a, 'b> {
     let t = Arg::with_name(arg::TAG)
-        .help("Tag")
+        .help("Tag as NAME or NAME=VALUE")
         .value_name("TAG")
         .takes_value(true)
         .long(arg::TAG)
@@ -36,6 +75,28 @@ pub fn make_app<'a, 'b>() -> App<'a, 'b> {
         .required(true)
         .min_values(1);
 
+    let paths = Arg::with_name(arg::PATHS)
+        .help("Files")
+        .value_name("PATHS")
+        .takes_value(true)
+        .multiple(true)
+        .required(true)
+        .min_values(1);
+
+    let optional_paths = Arg::with_name(arg::PATHS)
+        .help("Directories or files within project (defaults to whole project)")
+        .value_name("PATHS")
+        .takes_value(true)
+        .multiple(true);
+
+    let dry_run = Arg::with_name(arg::DRY_RUN)
+        .help("Show changes that would be made without writing to database")
+        .long(arg::DRY_RUN);
+
+    let content = Arg::with_name(arg::CONTENT)
+        .help("Apply to file content so that every copy of file is affected")
+        .long(arg::CONTENT);
+
     App::new("Richard's Tagging Tool")
         .author(crate_authors!())
         .about("Maintains database of tags for files")
@@ -60,29 +121,72 @@ pub fn make_app<'a, 'b>() -> App<'a, 'b> {
         .subcommand(
             SubCommand::with_name(command::DELETE_TAG)
                 .about("Delete tag")
-                .arg(&t),
+                .arg(&t)
+                .arg(&dry_run),
         )
         .subcommand(
             SubCommand::with_name(command::SCAN)
-                .about("Scan project directory and populate database"),
+                .about("Scan project directory and populate database")
+                .arg(
+                    Arg::with_name(arg::FULL)
+                        .help("Rehash all files even if size and modification time are unchanged")
+                        .long(arg::FULL),
+                )
+                .arg(
+                    Arg::with_name(arg::JOBS)
+                        .help("Number of hashing threads (defaults to number of CPUs)")
+                        .value_name("JOBS")
+                        .takes_value(true)
+                        .long(arg::JOBS)
+                        .short("j"),
+                )
+                .arg(
+                    Arg::with_name(arg::JSON)
+                        .help("Print summary as JSON")
+                        .long(arg::JSON),
+                )
+                .arg(&dry_run)
+                .arg(&optional_paths),
         )
         .subcommand(
             SubCommand::with_name(command::SEARCH)
                 .about("Search files by tag")
-                .arg(&t),
+                .arg(
+                    Arg::with_name(arg::TAG)
+                        .help("Tag (all tags must match)")
+                        .value_name("TAG")
+                        .takes_value(true)
+                        .long(arg::TAG)
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name(arg::QUERY)
+                        .help("Query expression, e.g. \"kick AND (808 OR 909) AND NOT acoustic AND bpm>=120\"")
+                        .value_name("QUERY")
+                        .takes_value(true)
+                        .multiple(true)
+                        .required_unless(arg::TAG),
+                ),
         )
         .subcommand(
             SubCommand::with_name(command::TAG)
                 .about("Tag files")
-                .arg(t)
+                .arg(&t)
+                .arg(&paths)
+                .arg(&content)
+                .arg(&dry_run),
+        )
+        .subcommand(
+            SubCommand::with_name(command::UNTAG)
+                .about("Remove tags from files")
+                .arg(&t)
+                .arg(&paths)
+                .arg(&content)
                 .arg(
-                    Arg::with_name(arg::PATHS)
-                        .help("Files")
-                        .value_name("PATHS")
-                        .takes_value(true)
-                        .multiple(true)
-                        .required(true)
-                        .min_values(1),
+                    Arg::with_name(arg::DELETE_UNUSED_TAGS)
+                        .help("Delete tags that are no longer applied to any files")
+                        .long(arg::DELETE_UNUSED_TAGS),
                 ),
         )
         // New commands
@@ -107,6 +211,22 @@ pub fn make_app<'a, 'b>() -> App<'a, 'b> {
                         .takes_value(true)
                         .long(arg::LIKE)
                         .required(false),
+                )
+                .arg(
+                    Arg::with_name(arg::MIN_SIZE)
+                        .help("Only show files of at least this many bytes")
+                        .value_name("BYTES")
+                        .takes_value(true)
+                        .long(arg::MIN_SIZE)
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name(arg::MAX_SIZE)
+                        .help("Only show files of at most this many bytes")
+                        .value_name("BYTES")
+                        .takes_value(true)
+                        .long(arg::MAX_SIZE)
+                        .required(false),
                 ),
         )
         .subcommand(
@@ -119,6 +239,193 @@ pub fn make_app<'a, 'b>() -> App<'a, 'b> {
                         .takes_value(true)
                         .long(arg::LIKE)
                         .required(false),
+                )
+                .arg(
+                    Arg::with_name(arg::TREE)
+                        .help("Show tag hierarchy as a tree")
+                        .long(arg::TREE),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(command::MERGE_TAGS)
+                .about("Merge tags into a single tag")
+                .usage("merge-tags <SRC>... INTO <DST>")
+                .arg(
+                    Arg::with_name(arg::TAGS)
+                        .help("Source tags followed by INTO and target tag")
+                        .value_name("TAGS")
+                        .takes_value(true)
+                        .multiple(true)
+                        .required(true)
+                        .min_values(3),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(command::RENAME_TAG)
+                .about("Rename tag")
+                .arg(
+                    Arg::with_name(arg::OLD_TAG)
+                        .help("Existing tag name")
+                        .value_name("OLD")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name(arg::NEW_TAG)
+                        .help("New tag name")
+                        .value_name("NEW")
+                        .takes_value(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(command::ADD_ALIAS)
+                .about("Add alias for tag")
+                .arg(
+                    Arg::with_name(arg::ALIAS)
+                        .help("Alias name")
+                        .value_name("ALIAS")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name(arg::TAG)
+                        .help("Tag name")
+                        .value_name("TAG")
+                        .takes_value(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(command::REMOVE_ALIAS)
+                .about("Remove tag aliases")
+                .arg(
+                    Arg::with_name(arg::ALIASES)
+                        .help("Alias names")
+                        .value_name("ALIASES")
+                        .takes_value(true)
+                        .multiple(true)
+                        .required(true)
+                        .min_values(1),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(command::LIST_ALIASES)
+                .about("Show tag aliases in database")
+                .arg(
+                    Arg::with_name(arg::LIKE)
+                        .help("Match alias names using SQL-style LIKE filter")
+                        .value_name("LIKE")
+                        .takes_value(true)
+                        .long(arg::LIKE)
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(command::TAG_INFO)
+                .about("Show or update tag description, color and category")
+                .arg(
+                    Arg::with_name(arg::TAG)
+                        .help("Tag name")
+                        .value_name("TAG")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name(arg::DESCRIPTION)
+                        .help("Description (empty to clear)")
+                        .value_name("DESCRIPTION")
+                        .takes_value(true)
+                        .long(arg::DESCRIPTION)
+                        .empty_values(true),
+                )
+                .arg(
+                    Arg::with_name(arg::COLOR)
+                        .help("Display color, e.g. \"bright red\" or \"#ff8800\" (empty to clear)")
+                        .value_name("COLOR")
+                        .takes_value(true)
+                        .long(arg::COLOR)
+                        .empty_values(true),
+                )
+                .arg(
+                    Arg::with_name(arg::CATEGORY)
+                        .help("Category (empty to clear)")
+                        .value_name("CATEGORY")
+                        .takes_value(true)
+                        .long(arg::CATEGORY)
+                        .empty_values(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(command::PRUNE)
+                .about("Delete files marked as missing by scan from database")
+                .arg(
+                    Arg::with_name(arg::OLDER_THAN)
+                        .help("Only prune files last seen more than this many days ago (0 for all)")
+                        .value_name("DAYS")
+                        .takes_value(true)
+                        .long(arg::OLDER_THAN)
+                        .default_value("30"),
+                )
+                .arg(&optional_paths),
+        )
+        .subcommand(
+            SubCommand::with_name(command::REHASH)
+                .about("Recompute signatures with given algorithm and make it project default")
+                .arg(
+                    Arg::with_name(arg::ALGORITHM)
+                        .help("Signature algorithm")
+                        .value_name("ALGORITHM")
+                        .possible_values(&Algorithm::NAMES)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(command::VERIFY)
+                .about("Recompute signatures of tracked files to detect corruption")
+                .arg(
+                    Arg::with_name(arg::DAYS)
+                        .help("Only verify files not verified within this many days")
+                        .value_name("DAYS")
+                        .takes_value(true)
+                        .long(arg::DAYS),
+                )
+                .arg(
+                    Arg::with_name(arg::RESUME)
+                        .help("Resume interrupted verification")
+                        .long(arg::RESUME),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(command::SIMILAR)
+                .about("List clusters of WAV and AIFF files that sound nearly the same")
+                .arg(
+                    Arg::with_name(arg::THRESHOLD)
+                        .help("Minimum similarity between 0 and 1")
+                        .value_name("THRESHOLD")
+                        .takes_value(true)
+                        .long(arg::THRESHOLD)
+                        .default_value("0.95"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(command::FILTERS)
+                .about("Show or update file extensions and directories considered by scan")
+                .arg(
+                    Arg::with_name(arg::INCLUDE)
+                        .help("Comma-separated file extensions to include (empty for default)")
+                        .value_name("EXTENSIONS")
+                        .takes_value(true)
+                        .long(arg::INCLUDE)
+                        .empty_values(true),
+                )
+                .arg(
+                    Arg::with_name(arg::EXCLUDE)
+                        .help("Comma-separated directory names to exclude (empty for default)")
+                        .value_name("DIRECTORIES")
+                        .takes_value(true)
+                        .long(arg::EXCLUDE)
+                        .empty_values(true),
                 ),
         )
 }
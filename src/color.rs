@@ -0,0 +1,58 @@
+use colored::{Color, Colorize};
+use std::str::FromStr;
+
+use crate::result::{user_error_result, Result};
+
+// Parses color name as understood by "colored" or "#rrggbb" hex triplet
+pub fn parse_color(s: &str) -> Result<Color> {
+    if s.len() == 7 && s.starts_with('#') {
+        let component = |i: usize| u8::from_str_radix(&s[i..i + 2], 16);
+        if let (Ok(r), Ok(g), Ok(b)) = (component(1), component(3), component(5)) {
+            return Ok(Color::TrueColor { r: r, g: g, b: b });
+        }
+    }
+    match Color::from_str(s) {
+        Ok(c) => Ok(c),
+        Err(_) => user_error_result(format!(
+            "Invalid color \"{}\": expected color name such as \"bright red\" or hex value such as \"#ff8800\"",
+            s
+        )),
+    }
+}
+
+pub fn is_terminal() -> bool {
+    atty::is(atty::Stream::Stdout)
+}
+
+// Applies color only when writing to a terminal so that piped output stays plain
+pub fn colorize(s: &str, color: &Option<String>) -> String {
+    match color {
+        Some(c) if is_terminal() => match parse_color(c) {
+            Ok(c) => s.color(c).to_string(),
+            Err(_) => s.to_string(),
+        },
+        _ => s.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_color() -> Result<()> {
+        assert_eq!(Color::Red, parse_color("red")?);
+        assert_eq!(Color::BrightBlue, parse_color("bright blue")?);
+        assert_eq!(
+            Color::TrueColor {
+                r: 255,
+                g: 136,
+                b: 0
+            },
+            parse_color("#ff8800")?
+        );
+        assert!(parse_color("#ff88zz").is_err());
+        assert!(parse_color("not-a-color").is_err());
+        Ok(())
+    }
+}
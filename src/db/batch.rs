@@ -0,0 +1,99 @@
+use rusqlite::{Connection, Transaction};
+
+use crate::result::Result;
+
+// Groups writes into transactions that are committed every "size" operations so that
+// long-running operations are fast and a failure only rolls back the current batch
+pub struct Batch<'conn> {
+    conn: &'conn Connection,
+    tx: Option<Transaction<'conn>>,
+    size: usize,
+    count: usize,
+}
+
+impl<'conn> Batch<'conn> {
+    pub fn new(conn: &'conn Connection, size: usize) -> Result<Self> {
+        Ok(Self {
+            conn: conn,
+            tx: Some(conn.unchecked_transaction()?),
+            size: size.max(1),
+            count: 0,
+        })
+    }
+
+    pub fn conn(&self) -> &Connection {
+        self.conn
+    }
+
+    pub fn tick(&mut self) -> Result<()> {
+        self.count += 1;
+        if self.count >= self.size {
+            if let Some(tx) = self.tx.take() {
+                tx.commit()?;
+            }
+            self.tx = Some(self.conn.unchecked_transaction()?);
+            self.count = 0;
+        }
+        Ok(())
+    }
+
+    pub fn commit(mut self) -> Result<()> {
+        if let Some(tx) = self.tx.take() {
+            tx.commit()?;
+        }
+        Ok(())
+    }
+
+    pub fn rollback(mut self) -> Result<()> {
+        if let Some(tx) = self.tx.take() {
+            tx.rollback()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::NO_PARAMS;
+
+    use super::*;
+
+    fn count(conn: &Connection) -> Result<i64> {
+        Ok(conn.query_row("SELECT COUNT(*) FROM t", NO_PARAMS, |row| row.get(0))?)
+    }
+
+    #[test]
+    fn test_batch() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        conn.execute_batch("CREATE TABLE t (id INTEGER PRIMARY KEY)")?;
+
+        {
+            let mut batch = Batch::new(&conn, 2)?;
+            for _ in 0..3 {
+                batch
+                    .conn()
+                    .execute("INSERT INTO t DEFAULT VALUES", NO_PARAMS)?;
+                batch.tick()?;
+            }
+            // Dropping batch rolls back uncommitted third insert
+        }
+        assert_eq!(2, count(&conn)?);
+
+        let mut batch = Batch::new(&conn, 10)?;
+        batch
+            .conn()
+            .execute("INSERT INTO t DEFAULT VALUES", NO_PARAMS)?;
+        batch.tick()?;
+        batch.commit()?;
+        assert_eq!(3, count(&conn)?);
+
+        let mut batch = Batch::new(&conn, usize::MAX)?;
+        batch
+            .conn()
+            .execute("INSERT INTO t DEFAULT VALUES", NO_PARAMS)?;
+        batch.tick()?;
+        batch.rollback()?;
+        assert_eq!(3, count(&conn)?);
+        Ok(())
+    }
+}
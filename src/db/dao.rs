@@ -4,32 +4,71 @@ use std::rc::Rc;
 
 use super::util::make_like_expression;
 use crate::file_info;
+use crate::fingerprint::Fingerprint;
 use crate::like::Like;
 use crate::location::Location;
-use crate::result::Result;
-use crate::signature::Signature;
+use crate::result::{user_error_result, Result};
+use crate::signature::{QuickSignature, Signature};
 use crate::tag;
 
 type Id = i64;
 
+// Query for files whose columns File::query_single and File::query_multi read in order
+const FILE_SELECT: &str = "SELECT files.id, files.location, files.content_id, contents.signature, files.size, files.mtime, files.missing, files.last_seen, files.quick_signature, files.last_verified, files.fingerprint FROM files INNER JOIN contents ON contents.id = files.content_id";
+
+// Instance of content at a location
 #[derive(Debug)]
 pub struct File {
     pub id: Id,
     pub location: Location,
+    pub content_id: Id,
     pub signature: Signature,
+    pub stat: Option<file_info::FileStat>,
+    pub missing: bool,
+    // Time file was last found by scan in seconds since Unix epoch
+    pub last_seen: Option<i64>,
+    pub quick_signature: Option<QuickSignature>,
+    // Time signature was last confirmed by verify in seconds since Unix epoch
+    pub last_verified: Option<i64>,
+    // Computed on demand by similar and cleared when content changes
+    pub fingerprint: Option<Fingerprint>,
+}
+
+// Outcome of inserting or updating a row so that callers can tell what changed without
+// inspecting database errors
+#[derive(Debug)]
+pub enum UpsertOutcome {
+    Inserted(Id),
+    // New location whose content is already found at other locations
+    Copied { id: Id, copies: Vec<File> },
+    // Location whose content has changed from content with given ID
+    Updated { id: Id, previous_content_id: Id },
+    Unchanged(Id),
 }
 
+// File content identified by signature regardless of how many locations it is found at
 #[derive(Debug)]
-pub struct DuplicateFile {
+pub struct Content {
     pub id: Id,
-    pub location: Location,
     pub signature: Signature,
+    pub size: Option<i64>,
 }
 
 #[derive(Debug)]
 pub struct Tag {
     pub id: Id,
     pub name: String,
+    pub parent_id: Option<Id>,
+    pub description: Option<String>,
+    pub color: Option<String>,
+    pub category: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct TagAlias {
+    pub id: Id,
+    pub name: String,
+    pub tag_id: Id,
 }
 
 #[derive(Debug)]
@@ -37,6 +76,23 @@ pub struct FileTag {
     pub id: Id,
     pub file_id: Id,
     pub tag_id: Id,
+    pub value: Option<tag::TagValue>,
+}
+
+// Project-wide setting stored in database
+#[derive(Debug)]
+pub struct Setting {
+    pub name: String,
+    pub value: String,
+}
+
+// Tag applied to content and therefore to every copy of it
+#[derive(Debug)]
+pub struct ContentTag {
+    pub id: Id,
+    pub content_id: Id,
+    pub tag_id: Id,
+    pub value: Option<tag::TagValue>,
 }
 
 fn to_sql_values(values: &Vec<&str>) -> Rc<Vec<Value>> {
@@ -49,64 +105,276 @@ fn to_sql_values(values: &Vec<&str>) -> Rc<Vec<Value>> {
     )
 }
 
+fn to_location_values(locations: &Vec<Location>) -> Rc<Vec<Value>> {
+    Rc::new(
+        locations
+            .iter()
+            .map(|x| Value::from(x.as_str().to_string()))
+            .collect::<Vec<Value>>(),
+    )
+}
+
+// Condition matching files at or underneath any of the locations bound to given parameter,
+// with empty location matching all files
+fn within_locations_condition(param: usize) -> String {
+    format!(
+        "EXISTS (SELECT 1 FROM RARRAY(?{0}) AS prefixes WHERE prefixes.value = '' OR files.location = prefixes.value OR substr(files.location, 1, length(prefixes.value) + 1) = prefixes.value || '/')",
+        param
+    )
+}
+
+fn stat_values(stat: &Option<file_info::FileStat>) -> (Option<i64>, Option<i64>) {
+    match stat {
+        Some(x) => (Some(x.size), Some(x.mtime)),
+        None => (None, None),
+    }
+}
+
+fn stat_from_values(size: Option<i64>, mtime: Option<i64>) -> Option<file_info::FileStat> {
+    match (size, mtime) {
+        (Some(size), Some(mtime)) => Some(file_info::FileStat {
+            size: size,
+            mtime: mtime,
+        }),
+        _ => None,
+    }
+}
+
 impl File {
     pub fn all(conn: &Connection, like: Option<Like>) -> Result<Vec<Self>> {
         let sql = match like {
             Some(l) => format!(
-                "SELECT id, location, signature FROM files WHERE location {}",
+                "{} WHERE files.location {}",
+                FILE_SELECT,
                 make_like_expression(&l)
             ),
-            None => String::from("SELECT id, location, signature FROM files"),
+            None => String::from(FILE_SELECT),
         };
         let mut stmt = conn.prepare(&sql)?;
         Self::query_multi(&mut stmt, NO_PARAMS)
     }
 
     pub fn all_by_location(conn: &Connection, location: &Location) -> Result<Vec<Self>> {
-        let mut stmt =
-            conn.prepare("SELECT id, location, signature FROM files WHERE location = ?1")?;
+        let mut stmt = conn.prepare(&format!("{} WHERE files.location = ?1", FILE_SELECT))?;
         Self::query_multi(&mut stmt, params![location])
     }
 
     pub fn all_by_locations(conn: &Connection, locations: &Vec<Location>) -> Result<Vec<Self>> {
-        let location_values = Rc::new(
-            locations
-                .iter()
-                .map(|x| Value::from(x.as_str().to_string()))
-                .collect::<Vec<Value>>(),
-        );
-        let mut stmt =
-            conn.prepare("SELECT id, location, signature FROM files WHERE location IN RARRAY(?1)")?;
+        let location_values = to_location_values(locations);
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE files.location IN RARRAY(?1)",
+            FILE_SELECT
+        ))?;
         Self::query_multi(&mut stmt, params![location_values])
     }
 
-    pub fn by_location(conn: &Connection, location: &Location) -> Result<Option<Self>> {
-        let mut stmt =
-            conn.prepare("SELECT id, location, signature FROM files WHERE location = ?1")?;
-        Self::query_single(&mut stmt, params![location])
+    // Files with tag applied either to file itself or to its content
+    pub fn all_by_tag_id(conn: &Connection, tag_id: Id) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE files.id IN (SELECT file_id FROM file_tags WHERE tag_id = ?1) OR files.content_id IN (SELECT content_id FROM content_tags WHERE tag_id = ?1) ORDER BY files.location",
+            FILE_SELECT
+        ))?;
+        Self::query_multi(&mut stmt, params![tag_id])
     }
 
-    pub fn by_signature(conn: &Connection, signature: &Signature) -> Result<Option<Self>> {
+    // All copies of content with given signature
+    pub fn all_by_signature(conn: &Connection, signature: &Signature) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare_cached(&format!(
+            "{} WHERE contents.signature = ?1 ORDER BY files.location",
+            FILE_SELECT
+        ))?;
+        Self::query_multi(&mut stmt, params![signature])
+    }
+
+    // Candidate copies of content with given quick signature
+    pub fn all_by_quick_signature(
+        conn: &Connection,
+        quick_signature: &QuickSignature,
+    ) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare_cached(&format!(
+            "{} WHERE files.quick_signature = ?1 ORDER BY files.location",
+            FILE_SELECT
+        ))?;
+        Self::query_multi(&mut stmt, params![quick_signature])
+    }
+
+    pub fn by_location(conn: &Connection, location: &Location) -> Result<Option<Self>> {
         let mut stmt =
-            conn.prepare("SELECT id, location, signature FROM files WHERE signature = ?1")?;
-        Self::query_single(&mut stmt, params![signature])
+            conn.prepare_cached(&format!("{} WHERE files.location = ?1", FILE_SELECT))?;
+        Self::query_single(&mut stmt, params![location])
     }
 
     pub fn insert(conn: &Connection, file_info: &file_info::FileInfo) -> Result<Id> {
+        let content_id =
+            Content::upsert(conn, &file_info.signature, file_info.stat.map(|x| x.size))?;
+        let (size, mtime) = stat_values(&file_info.stat);
         conn.execute(
-            "INSERT INTO files (location, signature) VALUES (?1, ?2)",
-            params![file_info.location, file_info.signature],
+            "INSERT INTO files (location, content_id, size, mtime, quick_signature) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                file_info.location,
+                content_id,
+                size,
+                mtime,
+                file_info.quick_signature
+            ],
         )?;
         Ok(conn.last_insert_rowid())
     }
 
-    pub fn upsert(conn: &Connection, file_info: &file_info::FileInfo) -> Result<Id> {
+    pub fn upsert(conn: &Connection, file_info: &file_info::FileInfo) -> Result<UpsertOutcome> {
+        let content_id =
+            Content::upsert(conn, &file_info.signature, file_info.stat.map(|x| x.size))?;
+        let existing = Self::by_location(conn, &file_info.location)?;
+        let (size, mtime) = stat_values(&file_info.stat);
+        let mut stmt = conn.prepare_cached(
+            "INSERT INTO files (location, content_id, size, mtime, quick_signature) VALUES (?1, ?2, ?3, ?4, ?5)
+                ON CONFLICT(location) DO UPDATE SET content_id = ?2, size = ?3, mtime = ?4, quick_signature = ?5,
+                    fingerprint = CASE WHEN content_id = ?2 THEN fingerprint ELSE NULL END",
+        )?;
+        stmt.execute(params![
+            file_info.location,
+            content_id,
+            size,
+            mtime,
+            file_info.quick_signature
+        ])?;
+        Ok(match existing {
+            Some(file) if file.content_id == content_id => UpsertOutcome::Unchanged(file.id),
+            Some(file) => UpsertOutcome::Updated {
+                id: file.id,
+                previous_content_id: file.content_id,
+            },
+            None => {
+                let id = conn.last_insert_rowid();
+                let copies = Self::all_by_signature(conn, &file_info.signature)?
+                    .into_iter()
+                    .filter(|x| x.id != id)
+                    .collect::<Vec<_>>();
+                if copies.is_empty() {
+                    UpsertOutcome::Inserted(id)
+                } else {
+                    UpsertOutcome::Copied { id, copies }
+                }
+            }
+        })
+    }
+
+    // Missing files last seen before given time or never seen at all
+    pub fn all_missing(
+        conn: &Connection,
+        last_seen_before: i64,
+        within: &Vec<Location>,
+    ) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE files.missing = 1 AND (files.last_seen IS NULL OR files.last_seen < ?1) AND {}",
+            FILE_SELECT,
+            within_locations_condition(2)
+        ))?;
+        Self::query_multi(
+            &mut stmt,
+            params![last_seen_before, to_location_values(within)],
+        )
+    }
+
+    // Files present at last scan that have not been verified since given time
+    pub fn all_unverified(conn: &Connection, verified_before: i64) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE files.missing = 0 AND (files.last_verified IS NULL OR files.last_verified < ?1) ORDER BY files.location",
+            FILE_SELECT
+        ))?;
+        Self::query_multi(&mut stmt, params![verified_before])
+    }
+
+    pub fn mark_verified(conn: &Connection, id: Id, timestamp: i64) -> Result<usize> {
+        let mut stmt = conn.prepare_cached("UPDATE files SET last_verified = ?2 WHERE id = ?1")?;
+        Ok(stmt.execute(params![id, timestamp])?)
+    }
+
+    pub fn mark_seen(
+        conn: &Connection,
+        locations: &Vec<Location>,
+        timestamp: i64,
+    ) -> Result<usize> {
+        Ok(conn.execute(
+            "UPDATE files SET missing = 0, last_seen = ?2 WHERE location IN RARRAY(?1)",
+            params![to_location_values(locations), timestamp],
+        )?)
+    }
+
+    // Marks files within given directory locations but not in given file locations as
+    // missing and returns newly missing files: files at or underneath excluded locations
+    // are left alone
+    pub fn mark_missing_except(
+        conn: &Connection,
+        locations: &Vec<Location>,
+        within: &Vec<Location>,
+        excluded: &Vec<Location>,
+    ) -> Result<Vec<Self>> {
+        let location_values = to_location_values(locations);
+        let within_values = to_location_values(within);
+        let excluded_values = to_location_values(excluded);
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE files.missing = 0 AND files.location NOT IN RARRAY(?1) AND {} AND NOT {}",
+            FILE_SELECT,
+            within_locations_condition(2),
+            within_locations_condition(3)
+        ))?;
+        let files = Self::query_multi(
+            &mut stmt,
+            params![location_values, within_values, excluded_values],
+        )?;
         conn.execute(
-            "INSERT INTO files (location, signature) VALUES (?1, ?2)
-                ON CONFLICT(location) DO UPDATE SET signature = ?2",
-            params![file_info.location, file_info.signature],
+            &format!(
+                "UPDATE files SET missing = 1 WHERE missing = 0 AND location NOT IN RARRAY(?1) AND {} AND NOT {}",
+                within_locations_condition(2),
+                within_locations_condition(3)
+            ),
+            params![location_values, within_values, excluded_values],
         )?;
-        Ok(conn.last_insert_rowid())
+        Ok(files)
+    }
+
+    pub fn delete_by_id(conn: &Connection, id: Id) -> Result<usize> {
+        conn.execute("DELETE FROM file_tags WHERE file_id = ?1", params![id])?;
+        Ok(conn.execute("DELETE FROM files WHERE id = ?1", params![id])?)
+    }
+
+    // Moves existing file to new location keeping its ID so that tags stay attached
+    pub fn update_location(
+        conn: &Connection,
+        id: Id,
+        file_info: &file_info::FileInfo,
+    ) -> Result<usize> {
+        let (size, mtime) = stat_values(&file_info.stat);
+        let mut stmt = conn.prepare_cached(
+            "UPDATE files SET location = ?2, size = ?3, mtime = ?4, quick_signature = ?5 WHERE id = ?1",
+        )?;
+        Ok(stmt.execute(params![
+            id,
+            file_info.location,
+            size,
+            mtime,
+            file_info.quick_signature
+        ])?)
+    }
+
+    pub fn update_quick_signature(
+        conn: &Connection,
+        location: &Location,
+        quick_signature: &QuickSignature,
+    ) -> Result<usize> {
+        let mut stmt =
+            conn.prepare_cached("UPDATE files SET quick_signature = ?2 WHERE location = ?1")?;
+        Ok(stmt.execute(params![location, quick_signature])?)
+    }
+
+    pub fn update_fingerprint(
+        conn: &Connection,
+        id: Id,
+        fingerprint: &Fingerprint,
+    ) -> Result<usize> {
+        let mut stmt = conn.prepare_cached("UPDATE files SET fingerprint = ?2 WHERE id = ?1")?;
+        Ok(stmt.execute(params![id, fingerprint])?)
     }
 
     fn query_single(stmt: &mut Statement, params: &[&dyn ToSql]) -> Result<Option<Self>> {
@@ -115,7 +383,14 @@ impl File {
                 Ok(Self {
                     id: row.get(0)?,
                     location: row.get(1)?,
-                    signature: row.get(2)?,
+                    content_id: row.get(2)?,
+                    signature: row.get(3)?,
+                    stat: stat_from_values(row.get(4)?, row.get(5)?),
+                    missing: row.get(6)?,
+                    last_seen: row.get(7)?,
+                    quick_signature: row.get(8)?,
+                    last_verified: row.get(9)?,
+                    fingerprint: row.get(10)?,
                 })
             })
             .optional()?)
@@ -127,26 +402,88 @@ impl File {
                 Ok(Self {
                     id: row.get(0)?,
                     location: row.get(1)?,
-                    signature: row.get(2)?,
+                    content_id: row.get(2)?,
+                    signature: row.get(3)?,
+                    stat: stat_from_values(row.get(4)?, row.get(5)?),
+                    missing: row.get(6)?,
+                    last_seen: row.get(7)?,
+                    quick_signature: row.get(8)?,
+                    last_verified: row.get(9)?,
+                    fingerprint: row.get(10)?,
                 })
             })?
             .collect::<rusqlite::Result<_>>()?)
     }
 }
 
-impl DuplicateFile {
+impl Content {
     pub fn all(conn: &Connection) -> Result<Vec<Self>> {
-        let mut stmt = conn.prepare("SELECT id, location, signature FROM duplicate_files")?;
+        let mut stmt = conn.prepare("SELECT id, signature, size FROM contents")?;
         Self::query_multi(&mut stmt, NO_PARAMS)
     }
 
-    pub fn upsert(conn: &Connection, file_info: &file_info::FileInfo) -> Result<Id> {
+    pub fn by_signature(conn: &Connection, signature: &Signature) -> Result<Option<Self>> {
+        let mut stmt =
+            conn.prepare_cached("SELECT id, signature, size FROM contents WHERE signature = ?1")?;
+        Self::query_single(&mut stmt, params![signature])
+    }
+
+    pub fn upsert(conn: &Connection, signature: &Signature, size: Option<i64>) -> Result<Id> {
+        let mut stmt = conn.prepare_cached(
+            "INSERT INTO contents (signature, size) VALUES (?1, ?2)
+                ON CONFLICT(signature) DO UPDATE SET size = COALESCE(?2, size)",
+        )?;
+        stmt.execute(params![signature, size])?;
+        let mut stmt = conn.prepare_cached("SELECT id FROM contents WHERE signature = ?1")?;
+        Ok(stmt.query_row(params![signature], |row| row.get(0))?)
+    }
+
+    pub fn update_signature(conn: &Connection, id: Id, signature: &Signature) -> Result<usize> {
+        Ok(conn.execute(
+            "UPDATE contents SET signature = ?2 WHERE id = ?1",
+            params![id, signature],
+        )?)
+    }
+
+    // Moves copies and tags of one content to another and deletes it
+    pub fn merge(conn: &Connection, from_id: Id, to_id: Id) -> Result<usize> {
         conn.execute(
-            "INSERT INTO duplicate_files (location, signature) VALUES (?1, ?2)
-                ON CONFLICT(location) DO UPDATE SET signature = ?2",
-            params![file_info.location, file_info.signature],
+            "UPDATE files SET content_id = ?2 WHERE content_id = ?1",
+            params![from_id, to_id],
         )?;
-        Ok(conn.last_insert_rowid())
+        conn.execute(
+            "UPDATE OR IGNORE content_tags SET content_id = ?2 WHERE content_id = ?1",
+            params![from_id, to_id],
+        )?;
+        conn.execute(
+            "DELETE FROM content_tags WHERE content_id = ?1",
+            params![from_id],
+        )?;
+        Ok(conn.execute("DELETE FROM contents WHERE id = ?1", params![from_id])?)
+    }
+
+    // Deletes content no longer found at any location along with its tags
+    pub fn delete_unused(conn: &Connection) -> Result<usize> {
+        conn.execute(
+            "DELETE FROM content_tags WHERE content_id NOT IN (SELECT content_id FROM files)",
+            NO_PARAMS,
+        )?;
+        Ok(conn.execute(
+            "DELETE FROM contents WHERE id NOT IN (SELECT content_id FROM files)",
+            NO_PARAMS,
+        )?)
+    }
+
+    fn query_single(stmt: &mut Statement, params: &[&dyn ToSql]) -> Result<Option<Self>> {
+        Ok(stmt
+            .query_row(params, |row| {
+                Ok(Self {
+                    id: row.get(0)?,
+                    signature: row.get(1)?,
+                    size: row.get(2)?,
+                })
+            })
+            .optional()?)
     }
 
     fn query_multi(stmt: &mut Statement, params: &[&dyn ToSql]) -> Result<Vec<Self>> {
@@ -154,44 +491,199 @@ impl DuplicateFile {
             .query_map(params, |row| {
                 Ok(Self {
                     id: row.get(0)?,
-                    location: row.get(1)?,
-                    signature: row.get(2)?,
+                    signature: row.get(1)?,
+                    size: row.get(2)?,
                 })
             })?
             .collect::<rusqlite::Result<_>>()?)
     }
 }
 
+impl Setting {
+    pub fn by_name(conn: &Connection, name: &str) -> Result<Option<Self>> {
+        let mut stmt = conn.prepare("SELECT name, value FROM settings WHERE name = ?1")?;
+        Ok(stmt
+            .query_row(params![name], |row| {
+                Ok(Self {
+                    name: row.get(0)?,
+                    value: row.get(1)?,
+                })
+            })
+            .optional()?)
+    }
+
+    pub fn upsert(conn: &Connection, name: &str, value: &str) -> Result<()> {
+        conn.execute(
+            "INSERT INTO settings (name, value) VALUES (?1, ?2)
+                ON CONFLICT(name) DO UPDATE SET value = ?2",
+            params![name, value],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_by_name(conn: &Connection, name: &str) -> Result<usize> {
+        Ok(conn.execute("DELETE FROM settings WHERE name = ?1", params![name])?)
+    }
+}
+
 impl Tag {
     pub fn all(conn: &Connection, like: Option<Like>) -> Result<Vec<Self>> {
         let sql = match like {
             Some(l) => format!(
-                "SELECT id, name FROM tags WHERE name {}",
+                "SELECT id, name, parent_id, description, color, category FROM tags WHERE name {}",
                 make_like_expression(&l)
             ),
-            None => String::from("SELECT id, name FROM tags"),
+            None => {
+                String::from("SELECT id, name, parent_id, description, color, category FROM tags")
+            }
         };
         let mut stmt = conn.prepare(&sql)?;
         Self::query_multi(&mut stmt, NO_PARAMS)
     }
 
     pub fn all_by_names(conn: &Connection, names: &Vec<&str>) -> Result<Vec<Self>> {
-        let mut stmt = conn.prepare("SELECT id, name FROM tags WHERE name IN RARRAY(?1)")?;
+        let mut stmt =
+            conn.prepare("SELECT id, name, parent_id, description, color, category FROM tags WHERE name IN RARRAY(?1)")?;
         Self::query_multi(&mut stmt, params![to_sql_values(names)])
     }
 
+    pub fn by_name(conn: &Connection, name: &str) -> Result<Option<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, name, parent_id, description, color, category FROM tags WHERE name = ?1",
+        )?;
+        Self::query_single(&mut stmt, params![name])
+    }
+
+    // Tags applied to file itself or to its content
+    pub fn all_by_file_id(conn: &Connection, file_id: Id) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, name, parent_id, description, color, category FROM tags WHERE id IN (SELECT tag_id FROM file_tags WHERE file_id = ?1 UNION SELECT content_tags.tag_id FROM content_tags INNER JOIN files ON files.content_id = content_tags.content_id WHERE files.id = ?1) ORDER BY name",
+        )?;
+        Self::query_multi(&mut stmt, params![file_id])
+    }
+
+    // Tags applied to content regardless of location
+    pub fn all_by_content_id(conn: &Connection, content_id: Id) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, name, parent_id, description, color, category FROM tags WHERE id IN (SELECT tag_id FROM content_tags WHERE content_id = ?1) ORDER BY name",
+        )?;
+        Self::query_multi(&mut stmt, params![content_id])
+    }
+
+    // Tags underneath tag with given name at any depth
+    pub fn descendants(conn: &Connection, name: &str) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, name, parent_id, description, color, category FROM tags WHERE substr(name, 1, length(?1) + 1) = ?1 || '/' ORDER BY name",
+        )?;
+        Self::query_multi(&mut stmt, params![name])
+    }
+
+    pub fn children(conn: &Connection, id: Id) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare("SELECT id, name, parent_id, description, color, category FROM tags WHERE parent_id = ?1")?;
+        Self::query_multi(&mut stmt, params![id])
+    }
+
+    // Inserts tag and any missing ancestors and returns ID of tag
     pub fn upsert(conn: &Connection, tag: &tag::Tag) -> Result<Id> {
-        conn.execute(
-            "INSERT INTO tags (name) VALUES (?1)
+        let parent_id = match tag.parent() {
+            Some(p) => Some(Self::upsert(conn, &p)?),
+            None => None,
+        };
+        let mut stmt = conn.prepare_cached(
+            "INSERT INTO tags (name, parent_id) VALUES (?1, ?2)
                 ON CONFLICT(name) DO NOTHING",
-            params![tag.as_str()],
         )?;
-        Ok(conn.last_insert_rowid())
+        stmt.execute(params![tag.as_str(), parent_id])?;
+        let mut stmt = conn.prepare_cached("SELECT id FROM tags WHERE name = ?1")?;
+        Ok(stmt.query_row(params![tag.as_str()], |row| row.get(0))?)
+    }
+
+    // Renames tag and all of its descendants, creating any missing ancestors of the new name
+    pub fn rename(conn: &Connection, tag: &Self, new_tag: &tag::Tag) -> Result<()> {
+        let parent_id = match new_tag.parent() {
+            Some(p) => Some(Self::upsert(conn, &p)?),
+            None => None,
+        };
+        conn.execute(
+            "UPDATE tags SET name = ?2, parent_id = ?3 WHERE id = ?1",
+            params![tag.id, new_tag.as_str(), parent_id],
+        )?;
+        conn.execute(
+            "UPDATE tags SET name = ?2 || substr(name, length(?1) + 1)
+                WHERE substr(name, 1, length(?1) + 1) = ?1 || '/'",
+            params![tag.name, new_tag.as_str()],
+        )?;
+        Ok(())
+    }
+
+    pub fn update_info(conn: &Connection, tag: &Self) -> Result<usize> {
+        Ok(conn.execute(
+            "UPDATE tags SET description = ?2, color = ?3, category = ?4 WHERE id = ?1",
+            params![tag.id, tag.description, tag.color, tag.category],
+        )?)
+    }
+
+    pub fn delete_by_id(conn: &Connection, id: Id) -> Result<usize> {
+        match Self::query_single(
+            &mut conn.prepare(
+                "SELECT id, name, parent_id, description, color, category FROM tags WHERE id = ?1",
+            )?,
+            params![id],
+        )? {
+            Some(tag) => Self::delete_all(conn, &vec![tag]),
+            None => Ok(0),
+        }
     }
 
     pub fn delete_by_names(conn: &Connection, names: &Vec<&str>) -> Result<Vec<Self>> {
-        let mut stmt = conn.prepare("DELETE FROM tags WHERE name IN RARRAY(?1)")?;
-        Self::query_multi(&mut stmt, params![to_sql_values(names)])
+        let tags = Self::all_by_names(conn, names)?;
+        Self::delete_all(conn, &tags)?;
+        Ok(tags)
+    }
+
+    // Deletes tags along with their aliases and applications to files and content: since
+    // hierarchy is encoded in tag names, deleting a tag while keeping its children is refused
+    fn delete_all(conn: &Connection, tags: &Vec<Self>) -> Result<usize> {
+        for tag in tags {
+            if let Some(child) = Self::children(conn, tag.id)?
+                .into_iter()
+                .find(|x| !tags.iter().any(|y| y.id == x.id))
+            {
+                return user_error_result(format!(
+                    "Tag {} has child tag {}: delete, merge or rename it first",
+                    tag.name, child.name
+                ));
+            }
+        }
+        for tag in tags {
+            conn.execute("DELETE FROM file_tags WHERE tag_id = ?1", params![tag.id])?;
+            conn.execute(
+                "DELETE FROM content_tags WHERE tag_id = ?1",
+                params![tag.id],
+            )?;
+            conn.execute("DELETE FROM tag_aliases WHERE tag_id = ?1", params![tag.id])?;
+        }
+        Ok(conn.execute(
+            "DELETE FROM tags WHERE id IN RARRAY(?1)",
+            params![Rc::new(
+                tags.iter().map(|x| Value::from(x.id)).collect::<Vec<_>>()
+            )],
+        )?)
+    }
+
+    fn query_single(stmt: &mut Statement, params: &[&dyn ToSql]) -> Result<Option<Self>> {
+        Ok(stmt
+            .query_row(params, |row| {
+                Ok(Self {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    parent_id: row.get(2)?,
+                    description: row.get(3)?,
+                    color: row.get(4)?,
+                    category: row.get(5)?,
+                })
+            })
+            .optional()?)
     }
 
     fn query_multi(stmt: &mut Statement, params: &[&dyn ToSql]) -> Result<Vec<Self>> {
@@ -200,6 +692,81 @@ impl Tag {
                 Ok(Self {
                     id: row.get(0)?,
                     name: row.get(1)?,
+                    parent_id: row.get(2)?,
+                    description: row.get(3)?,
+                    color: row.get(4)?,
+                    category: row.get(5)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?)
+    }
+}
+
+impl TagAlias {
+    pub fn all(conn: &Connection, like: Option<Like>) -> Result<Vec<Self>> {
+        let sql = match like {
+            Some(l) => format!(
+                "SELECT id, name, tag_id FROM tag_aliases WHERE name {}",
+                make_like_expression(&l)
+            ),
+            None => String::from("SELECT id, name, tag_id FROM tag_aliases"),
+        };
+        let mut stmt = conn.prepare(&sql)?;
+        Self::query_multi(&mut stmt, NO_PARAMS)
+    }
+
+    pub fn by_name(conn: &Connection, name: &str) -> Result<Option<Self>> {
+        let mut stmt = conn.prepare("SELECT id, name, tag_id FROM tag_aliases WHERE name = ?1")?;
+        Self::query_single(&mut stmt, params![name])
+    }
+
+    pub fn insert(conn: &Connection, name: &str, tag_id: Id) -> Result<Id> {
+        conn.execute(
+            "INSERT INTO tag_aliases (name, tag_id) VALUES (?1, ?2)",
+            params![name, tag_id],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn delete_by_name(conn: &Connection, name: &str) -> Result<usize> {
+        Ok(conn.execute("DELETE FROM tag_aliases WHERE name = ?1", params![name])?)
+    }
+
+    // Maps each alias to the name of its canonical tag and passes other names through unchanged
+    pub fn resolve_names(conn: &Connection, names: &Vec<&str>) -> Result<Vec<String>> {
+        let mut stmt = conn.prepare_cached(
+            "SELECT tags.name FROM tag_aliases INNER JOIN tags ON tags.id = tag_aliases.tag_id WHERE tag_aliases.name = ?1",
+        )?;
+        names
+            .iter()
+            .map(|&name| {
+                Ok(stmt
+                    .query_row(params![name], |row| row.get(0))
+                    .optional()?
+                    .unwrap_or_else(|| String::from(name)))
+            })
+            .collect()
+    }
+
+    fn query_single(stmt: &mut Statement, params: &[&dyn ToSql]) -> Result<Option<Self>> {
+        Ok(stmt
+            .query_row(params, |row| {
+                Ok(Self {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    tag_id: row.get(2)?,
+                })
+            })
+            .optional()?)
+    }
+
+    fn query_multi(stmt: &mut Statement, params: &[&dyn ToSql]) -> Result<Vec<Self>> {
+        Ok(stmt
+            .query_map(params, |row| {
+                Ok(Self {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    tag_id: row.get(2)?,
                 })
             })?
             .collect::<rusqlite::Result<_>>()?)
@@ -208,19 +775,72 @@ impl Tag {
 
 impl FileTag {
     pub fn all(conn: &Connection) -> Result<Vec<Self>> {
-        let mut stmt = conn.prepare("SELECT id, file_id, tag_id FROM file_tags")?;
+        let mut stmt = conn.prepare("SELECT id, file_id, tag_id, value FROM file_tags")?;
         Self::query_multi(&mut stmt, NO_PARAMS)
     }
 
-    pub fn upsert(conn: &Connection, file_id: Id, tag_id: Id) -> Result<Id> {
-        let mut stmt = conn.prepare(
-            "INSERT INTO file_tags (file_id, tag_id) VALUES (?1, ?2)
-                ON CONFLICT(file_id, tag_id) DO NOTHING",
+    pub fn count_by_tag_id(conn: &Connection, tag_id: Id) -> Result<i64> {
+        Ok(conn.query_row(
+            "SELECT COUNT(*) FROM file_tags WHERE tag_id = ?1",
+            params![tag_id],
+            |row| row.get(0),
+        )?)
+    }
+
+    pub fn by_ids(conn: &Connection, file_id: Id, tag_id: Id) -> Result<Option<Self>> {
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, file_id, tag_id, value FROM file_tags WHERE file_id = ?1 AND tag_id = ?2",
+        )?;
+        Self::query_single(&mut stmt, params![file_id, tag_id])
+    }
+
+    pub fn delete(conn: &Connection, file_id: Id, tag_id: Id) -> Result<usize> {
+        Ok(conn.execute(
+            "DELETE FROM file_tags WHERE file_id = ?1 AND tag_id = ?2",
+            params![file_id, tag_id],
+        )?)
+    }
+
+    // Moves all associations from one tag to another, collapsing any that already exist
+    pub fn reassign(conn: &Connection, from_tag_id: Id, to_tag_id: Id) -> Result<usize> {
+        let count = conn.execute(
+            "UPDATE OR IGNORE file_tags SET tag_id = ?2 WHERE tag_id = ?1",
+            params![from_tag_id, to_tag_id],
         )?;
-        stmt.execute(params![file_id, tag_id])?;
+        conn.execute(
+            "DELETE FROM file_tags WHERE tag_id = ?1",
+            params![from_tag_id],
+        )?;
+        Ok(count)
+    }
+
+    pub fn upsert(
+        conn: &Connection,
+        file_id: Id,
+        tag_id: Id,
+        value: Option<&tag::TagValue>,
+    ) -> Result<Id> {
+        let mut stmt = conn.prepare_cached(
+            "INSERT INTO file_tags (file_id, tag_id, value) VALUES (?1, ?2, ?3)
+                ON CONFLICT(file_id, tag_id) DO UPDATE SET value = ?3",
+        )?;
+        stmt.execute(params![file_id, tag_id, value])?;
         Ok(conn.last_insert_rowid())
     }
 
+    fn query_single(stmt: &mut Statement, params: &[&dyn ToSql]) -> Result<Option<Self>> {
+        Ok(stmt
+            .query_row(params, |row| {
+                Ok(Self {
+                    id: row.get(0)?,
+                    file_id: row.get(1)?,
+                    tag_id: row.get(2)?,
+                    value: row.get(3)?,
+                })
+            })
+            .optional()?)
+    }
+
     fn query_multi(stmt: &mut Statement, params: &[&dyn ToSql]) -> Result<Vec<Self>> {
         Ok(stmt
             .query_map(params, |row| {
@@ -228,6 +848,99 @@ impl FileTag {
                     id: row.get(0)?,
                     file_id: row.get(1)?,
                     tag_id: row.get(2)?,
+                    value: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?)
+    }
+}
+
+impl ContentTag {
+    pub fn all(conn: &Connection) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare("SELECT id, content_id, tag_id, value FROM content_tags")?;
+        Self::query_multi(&mut stmt, NO_PARAMS)
+    }
+
+    pub fn count_by_tag_id(conn: &Connection, tag_id: Id) -> Result<i64> {
+        Ok(conn.query_row(
+            "SELECT COUNT(*) FROM content_tags WHERE tag_id = ?1",
+            params![tag_id],
+            |row| row.get(0),
+        )?)
+    }
+
+    pub fn by_ids(conn: &Connection, content_id: Id, tag_id: Id) -> Result<Option<Self>> {
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, content_id, tag_id, value FROM content_tags WHERE content_id = ?1 AND tag_id = ?2",
+        )?;
+        Self::query_single(&mut stmt, params![content_id, tag_id])
+    }
+
+    pub fn delete(conn: &Connection, content_id: Id, tag_id: Id) -> Result<usize> {
+        Ok(conn.execute(
+            "DELETE FROM content_tags WHERE content_id = ?1 AND tag_id = ?2",
+            params![content_id, tag_id],
+        )?)
+    }
+
+    // Moves all associations from one tag to another, collapsing any that already exist
+    pub fn reassign(conn: &Connection, from_tag_id: Id, to_tag_id: Id) -> Result<usize> {
+        let count = conn.execute(
+            "UPDATE OR IGNORE content_tags SET tag_id = ?2 WHERE tag_id = ?1",
+            params![from_tag_id, to_tag_id],
+        )?;
+        conn.execute(
+            "DELETE FROM content_tags WHERE tag_id = ?1",
+            params![from_tag_id],
+        )?;
+        Ok(count)
+    }
+
+    // Moves associations of content that is no longer found at any location to content
+    // that replaced it, collapsing any that already exist
+    pub fn carry_over(conn: &Connection, from_content_id: Id, to_content_id: Id) -> Result<usize> {
+        let mut stmt = conn.prepare_cached(
+            "UPDATE OR IGNORE content_tags SET content_id = ?2 WHERE content_id = ?1
+                AND NOT EXISTS (SELECT 1 FROM files WHERE content_id = ?1)",
+        )?;
+        Ok(stmt.execute(params![from_content_id, to_content_id])?)
+    }
+
+    pub fn upsert(
+        conn: &Connection,
+        content_id: Id,
+        tag_id: Id,
+        value: Option<&tag::TagValue>,
+    ) -> Result<Id> {
+        let mut stmt = conn.prepare_cached(
+            "INSERT INTO content_tags (content_id, tag_id, value) VALUES (?1, ?2, ?3)
+                ON CONFLICT(content_id, tag_id) DO UPDATE SET value = ?3",
+        )?;
+        stmt.execute(params![content_id, tag_id, value])?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    fn query_single(stmt: &mut Statement, params: &[&dyn ToSql]) -> Result<Option<Self>> {
+        Ok(stmt
+            .query_row(params, |row| {
+                Ok(Self {
+                    id: row.get(0)?,
+                    content_id: row.get(1)?,
+                    tag_id: row.get(2)?,
+                    value: row.get(3)?,
+                })
+            })
+            .optional()?)
+    }
+
+    fn query_multi(stmt: &mut Statement, params: &[&dyn ToSql]) -> Result<Vec<Self>> {
+        Ok(stmt
+            .query_map(params, |row| {
+                Ok(Self {
+                    id: row.get(0)?,
+                    content_id: row.get(1)?,
+                    tag_id: row.get(2)?,
+                    value: row.get(3)?,
                 })
             })?
             .collect::<rusqlite::Result<_>>()?)
@@ -240,6 +953,7 @@ mod tests {
 
     use super::*;
     use crate::db::run_migrations;
+    use crate::test_util::make_signature;
 
     #[test]
     fn basics() -> Result<()> {
@@ -248,27 +962,65 @@ mod tests {
         run_migrations(&conn)?;
 
         assert!(File::all(&conn, None)?.is_empty());
-        assert!(DuplicateFile::all(&conn)?.is_empty());
+        assert!(Content::all(&conn)?.is_empty());
         assert!(Tag::all(&conn, None)?.is_empty());
         assert!(FileTag::all(&conn)?.is_empty());
 
         File::insert(
             &conn,
-            &file_info::FileInfo::new(
-                Location::try_from("LOCATION0")?,
-                Signature::try_from("SIGNATURE0")?,
-            ),
+            &file_info::FileInfo::new(Location::try_from("LOCATION0")?, make_signature(0)),
         )?;
         File::insert(
             &conn,
-            &file_info::FileInfo::new(
-                Location::try_from("LOCATION1")?,
-                Signature::try_from("SIGNATURE1")?,
-            ),
+            &file_info::FileInfo::new(Location::try_from("LOCATION1")?, make_signature(1)),
         )?;
 
         assert_eq!(2, File::all(&conn, None)?.len());
-        assert!(DuplicateFile::all(&conn)?.is_empty());
+        assert_eq!(2, Content::all(&conn)?.len());
+
+        let mut file_info =
+            file_info::FileInfo::new(Location::try_from("LOCATION1")?, make_signature(1));
+        assert!(File::by_location(&conn, &file_info.location)?
+            .unwrap()
+            .stat
+            .is_none());
+        file_info.stat = Some(file_info::FileStat {
+            size: 100,
+            mtime: 200,
+        });
+        File::upsert(&conn, &file_info)?;
+        assert_eq!(
+            file_info.stat,
+            File::by_location(&conn, &file_info.location)?.unwrap().stat
+        );
+
+        let locations = vec![Location::try_from("LOCATION0")?];
+        let everywhere = vec![Location::try_from("")?];
+        let elsewhere = vec![Location::try_from("OTHER")?];
+        assert_eq!(1, File::mark_seen(&conn, &locations, 1000)?);
+        let none = Vec::new();
+        assert!(File::mark_missing_except(&conn, &locations, &elsewhere, &none)?.is_empty());
+        assert!(File::mark_missing_except(&conn, &locations, &everywhere, &everywhere)?.is_empty());
+        let missing = File::mark_missing_except(&conn, &locations, &everywhere, &none)?;
+        assert_eq!(1, missing.len());
+        assert_eq!("LOCATION1", missing[0].location.as_str());
+        assert!(File::mark_missing_except(&conn, &locations, &everywhere, &none)?.is_empty());
+        assert_eq!(1, File::all_missing(&conn, 0, &everywhere)?.len());
+        assert!(File::all_missing(&conn, 0, &elsewhere)?.is_empty());
+        let file = File::by_location(&conn, &locations[0])?.unwrap();
+        assert!(!file.missing);
+        assert_eq!(Some(1000), file.last_seen);
+
+        let id = File::by_location(&conn, &file_info.location)?.unwrap().id;
+        file_info.location = Location::try_from("LOCATION1-MOVED")?;
+        assert_eq!(1, File::update_location(&conn, id, &file_info)?);
+        assert!(File::by_location(&conn, &Location::try_from("LOCATION1")?)?.is_none());
+        assert_eq!(
+            id,
+            File::by_location(&conn, &file_info.location)?.unwrap().id
+        );
+        file_info.location = Location::try_from("LOCATION1")?;
+        File::update_location(&conn, id, &file_info)?;
 
         assert_eq!(
             1,
@@ -302,17 +1054,389 @@ mod tests {
         assert_eq!(2, tags[1].id);
         assert_eq!("tag1", tags[1].name);
 
-        DuplicateFile::upsert(
-            &conn,
-            &file_info::FileInfo::new(
+        FileTag::upsert(&conn, 1, tags[0].id, None)?;
+        FileTag::upsert(&conn, 2, tags[0].id, None)?;
+        assert_eq!(2, FileTag::count_by_tag_id(&conn, tags[0].id)?);
+        assert_eq!(1, FileTag::delete(&conn, 1, tags[0].id)?);
+        assert_eq!(0, FileTag::delete(&conn, 1, tags[0].id)?);
+        assert_eq!(1, FileTag::count_by_tag_id(&conn, tags[0].id)?);
+
+        assert_eq!(1, Tag::delete_by_id(&conn, tags[1].id)?);
+        assert_eq!(2, Tag::all(&conn, None)?.len());
+
+        let tag2 = Tag::by_name(&conn, "tag2")?.unwrap();
+        FileTag::upsert(&conn, 1, tag2.id, None)?;
+        FileTag::upsert(&conn, 2, tag2.id, None)?;
+        assert_eq!(1, FileTag::reassign(&conn, tag2.id, tags[0].id)?);
+        assert_eq!(0, FileTag::count_by_tag_id(&conn, tag2.id)?);
+        assert_eq!(2, FileTag::count_by_tag_id(&conn, tags[0].id)?);
+
+        FileTag::upsert(&conn, 1, tag2.id, Some(&tag::TagValue::Integer(120)))?;
+        FileTag::upsert(&conn, 1, tags[0].id, Some(&tag::TagValue::Decimal(1.5)))?;
+        let values = FileTag::all(&conn)?
+            .into_iter()
+            .map(|x| x.value)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                None,
+                Some(tag::TagValue::Decimal(1.5)),
+                Some(tag::TagValue::Integer(120))
+            ],
+            values
+        );
+
+        assert_eq!(2, Tag::all_by_file_id(&conn, 1)?.len());
+
+        let mut tag0 = Tag::by_name(&conn, "tag0")?.unwrap();
+        assert_eq!(None, tag0.color);
+        tag0.description = Some(String::from("DESCRIPTION"));
+        tag0.color = Some(String::from("red"));
+        tag0.category = Some(String::from("CATEGORY"));
+        Tag::update_info(&conn, &tag0)?;
+        let tag0 = Tag::by_name(&conn, "tag0")?.unwrap();
+        assert_eq!(Some(String::from("DESCRIPTION")), tag0.description);
+        assert_eq!(Some(String::from("red")), tag0.color);
+        assert_eq!(Some(String::from("CATEGORY")), tag0.category);
+
+        Tag::rename(&conn, &tag2, &tag::Tag::from("tag3"))?;
+        assert!(Tag::by_name(&conn, "tag2")?.is_none());
+        assert_eq!(tag2.id, Tag::by_name(&conn, "tag3")?.unwrap().id);
+
+        Ok(())
+    }
+
+    #[test]
+    fn upsert_outcomes() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        rusqlite::vtab::array::load_module(&conn)?;
+        run_migrations(&conn)?;
+
+        let file_info = |location: &str, signature: u8| -> Result<file_info::FileInfo> {
+            Ok(file_info::FileInfo::new(
+                Location::try_from(location)?,
+                make_signature(signature),
+            ))
+        };
+
+        let id = match File::upsert(&conn, &file_info("LOCATION0", 0)?)? {
+            UpsertOutcome::Inserted(id) => id,
+            outcome => panic!("Unexpected outcome {:?}", outcome),
+        };
+        assert!(matches!(
+            File::upsert(&conn, &file_info("LOCATION0", 0)?)?,
+            UpsertOutcome::Unchanged(x) if x == id
+        ));
+        let content_id = File::by_location(&conn, &Location::try_from("LOCATION0")?)?
+            .unwrap()
+            .content_id;
+        assert!(matches!(
+            File::upsert(&conn, &file_info("LOCATION0", 1)?)?,
+            UpsertOutcome::Updated { id: x, previous_content_id: y } if x == id && y == content_id
+        ));
+        match File::upsert(&conn, &file_info("LOCATION1", 1)?)? {
+            UpsertOutcome::Copied { id: x, copies } => {
+                assert_ne!(id, x);
+                assert_eq!(1, copies.len());
+                assert_eq!("LOCATION0", copies[0].location.as_str());
+            }
+            outcome => panic!("Unexpected outcome {:?}", outcome),
+        }
+        assert!(matches!(
+            File::upsert(&conn, &file_info("LOCATION2", 2)?)?,
+            UpsertOutcome::Inserted(x) if x != id
+        ));
+        assert_eq!(3, File::all(&conn, None)?.len());
+        assert_eq!(3, Content::all(&conn)?.len());
+        assert_eq!(1, Content::delete_unused(&conn)?);
+        assert_eq!(2, Content::all(&conn)?.len());
+        Ok(())
+    }
+
+    #[test]
+    fn carry_over_content_tags() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        rusqlite::vtab::array::load_module(&conn)?;
+        run_migrations(&conn)?;
+
+        let file_info = |location: &str, signature: u8| -> Result<file_info::FileInfo> {
+            Ok(file_info::FileInfo::new(
+                Location::try_from(location)?,
+                make_signature(signature),
+            ))
+        };
+        let tag_id = Tag::upsert(&conn, &tag::Tag::from("tag0"))?;
+
+        File::insert(&conn, &file_info("LOCATION0", 0)?)?;
+        File::insert(&conn, &file_info("LOCATION1", 0)?)?;
+        let content_id0 = Content::by_signature(&conn, &make_signature(0))?
+            .unwrap()
+            .id;
+        ContentTag::upsert(&conn, content_id0, tag_id, None)?;
+
+        // Content still found at another location keeps its tags
+        File::upsert(&conn, &file_info("LOCATION0", 1)?)?;
+        let content_id1 = Content::by_signature(&conn, &make_signature(1))?
+            .unwrap()
+            .id;
+        assert_eq!(0, ContentTag::carry_over(&conn, content_id0, content_id1)?);
+
+        File::upsert(&conn, &file_info("LOCATION1", 1)?)?;
+        assert_eq!(1, ContentTag::carry_over(&conn, content_id0, content_id1)?);
+        assert_eq!(1, Content::delete_unused(&conn)?);
+        assert_eq!(
+            vec![String::from("tag0")],
+            Tag::all_by_content_id(&conn, content_id1)?
+                .into_iter()
+                .map(|x| x.name)
+                .collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn fingerprints() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        rusqlite::vtab::array::load_module(&conn)?;
+        run_migrations(&conn)?;
+
+        let file_info = |signature: u8| -> Result<file_info::FileInfo> {
+            Ok(file_info::FileInfo::new(
                 Location::try_from("LOCATION0")?,
-                Signature::try_from("SIGNATURE0")?,
-            ),
+                make_signature(signature),
+            ))
+        };
+        let fingerprint = Fingerprint::try_from(format!("100:{}", "80".repeat(64)).as_str())?;
+
+        let id = File::insert(&conn, &file_info(0)?)?;
+        assert!(File::by_location(&conn, &Location::try_from("LOCATION0")?)?
+            .unwrap()
+            .fingerprint
+            .is_none());
+        assert_eq!(1, File::update_fingerprint(&conn, id, &fingerprint)?);
+        assert_eq!(
+            Some(&fingerprint),
+            File::by_location(&conn, &Location::try_from("LOCATION0")?)?
+                .unwrap()
+                .fingerprint
+                .as_ref()
+        );
+
+        // Fingerprint is kept while content is unchanged and cleared when it changes
+        File::upsert(&conn, &file_info(0)?)?;
+        assert!(File::by_location(&conn, &Location::try_from("LOCATION0")?)?
+            .unwrap()
+            .fingerprint
+            .is_some());
+        File::upsert(&conn, &file_info(1)?)?;
+        assert!(File::by_location(&conn, &Location::try_from("LOCATION0")?)?
+            .unwrap()
+            .fingerprint
+            .is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn contents() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        rusqlite::vtab::array::load_module(&conn)?;
+        run_migrations(&conn)?;
+
+        let signature = make_signature(0);
+        let file_id0 = File::insert(
+            &conn,
+            &file_info::FileInfo::new(Location::try_from("PACK0/LOCATION")?, make_signature(0)),
+        )?;
+        let file_id1 = File::insert(
+            &conn,
+            &file_info::FileInfo::new(Location::try_from("PACK1/LOCATION")?, make_signature(0)),
         )?;
 
-        assert_eq!(2, File::all(&conn, None)?.len());
-        assert_eq!(1, DuplicateFile::all(&conn)?.len());
+        let copies = File::all_by_signature(&conn, &signature)?;
+        assert_eq!(2, copies.len());
+        assert_eq!(copies[0].content_id, copies[1].content_id);
+        let content = Content::by_signature(&conn, &signature)?.unwrap();
+        assert_eq!(content.id, copies[0].content_id);
 
+        // Rehashing content keeps its copies together
+        let new_signature = make_signature(1);
+        assert_eq!(
+            1,
+            Content::update_signature(&conn, content.id, &new_signature)?
+        );
+        assert!(File::all_by_signature(&conn, &signature)?.is_empty());
+        assert_eq!(2, File::all_by_signature(&conn, &new_signature)?.len());
+
+        let kick_id = Tag::upsert(&conn, &tag::Tag::from("kick"))?;
+        let pack_id = Tag::upsert(&conn, &tag::Tag::from("pack"))?;
+        ContentTag::upsert(&conn, content.id, kick_id, None)?;
+        FileTag::upsert(&conn, file_id0, pack_id, None)?;
+        assert_eq!(2, Tag::all_by_file_id(&conn, file_id0)?.len());
+        assert_eq!(1, Tag::all_by_file_id(&conn, file_id1)?.len());
+        assert_eq!(2, File::all_by_tag_id(&conn, kick_id)?.len());
+        assert_eq!(1, File::all_by_tag_id(&conn, pack_id)?.len());
+        assert_eq!(1, ContentTag::count_by_tag_id(&conn, kick_id)?);
+
+        // Merging content carries its tags over to other content
+        let other_id = Content::upsert(&conn, &make_signature(2), None)?;
+        ContentTag::upsert(
+            &conn,
+            other_id,
+            Tag::upsert(&conn, &tag::Tag::from("snare"))?,
+            None,
+        )?;
+        assert_eq!(1, Content::merge(&conn, other_id, content.id)?);
+        assert_eq!(1, Content::all(&conn)?.len());
+        assert_eq!(2, ContentTag::all(&conn)?.len());
+        assert_eq!(2, Tag::all_by_file_id(&conn, file_id1)?.len());
+
+        // Content and its tags are only deleted once no copies remain
+        File::delete_by_id(&conn, file_id0)?;
+        assert_eq!(0, Content::delete_unused(&conn)?);
+        File::delete_by_id(&conn, file_id1)?;
+        assert_eq!(1, Content::delete_unused(&conn)?);
+        assert!(ContentTag::all(&conn)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn unverified() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        rusqlite::vtab::array::load_module(&conn)?;
+        run_migrations(&conn)?;
+
+        let mut ids = Vec::new();
+        for (location, signature) in &[("LOCATION0", 0), ("LOCATION1", 1)] {
+            ids.push(File::insert(
+                &conn,
+                &file_info::FileInfo::new(
+                    Location::try_from(*location)?,
+                    make_signature(*signature),
+                ),
+            )?);
+        }
+        assert_eq!(2, File::all_unverified(&conn, 100)?.len());
+
+        File::mark_verified(&conn, ids[0], 50)?;
+        File::mark_verified(&conn, ids[1], 150)?;
+        let files = File::all_unverified(&conn, 100)?;
+        assert_eq!(1, files.len());
+        assert_eq!(Some(50), files[0].last_verified);
+        assert!(File::all_unverified(&conn, 50)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn settings() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        rusqlite::vtab::array::load_module(&conn)?;
+        run_migrations(&conn)?;
+
+        assert!(Setting::by_name(&conn, "NAME")?.is_none());
+        Setting::upsert(&conn, "NAME", "VALUE0")?;
+        Setting::upsert(&conn, "NAME", "VALUE1")?;
+        assert_eq!("VALUE1", Setting::by_name(&conn, "NAME")?.unwrap().value);
+        assert_eq!(1, Setting::delete_by_name(&conn, "NAME")?);
+        assert!(Setting::by_name(&conn, "NAME")?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn aliases() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        rusqlite::vtab::array::load_module(&conn)?;
+        run_migrations(&conn)?;
+
+        let tag_id = Tag::upsert(&conn, &tag::Tag::from("hihat"))?;
+        TagAlias::insert(&conn, "hh", tag_id)?;
+        TagAlias::insert(&conn, "hi-hat", tag_id)?;
+        assert!(TagAlias::insert(&conn, "hh", tag_id).is_err());
+        assert_eq!(2, TagAlias::all(&conn, None)?.len());
+        assert_eq!(tag_id, TagAlias::by_name(&conn, "hh")?.unwrap().tag_id);
+
+        assert_eq!(
+            vec!["hihat", "hihat", "hihat", "kick"],
+            TagAlias::resolve_names(&conn, &vec!["hh", "hi-hat", "hihat", "kick"])?
+        );
+
+        assert_eq!(1, TagAlias::delete_by_name(&conn, "hh")?);
+        assert_eq!(vec!["hh"], TagAlias::resolve_names(&conn, &vec!["hh"])?);
+
+        Tag::delete_by_id(&conn, tag_id)?;
+        assert!(TagAlias::all(&conn, None)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn hierarchy() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        rusqlite::vtab::array::load_module(&conn)?;
+        run_migrations(&conn)?;
+
+        let id = Tag::upsert(&conn, &tag::Tag::from("drums/kick/acoustic"))?;
+        assert_eq!(
+            id,
+            Tag::upsert(&conn, &tag::Tag::from("drums/kick/acoustic"))?
+        );
+        assert_eq!(3, Tag::all(&conn, None)?.len());
+
+        let drums = Tag::by_name(&conn, "drums")?.unwrap();
+        let kick = Tag::by_name(&conn, "drums/kick")?.unwrap();
+        let acoustic = Tag::by_name(&conn, "drums/kick/acoustic")?.unwrap();
+        assert_eq!(None, drums.parent_id);
+        assert_eq!(Some(drums.id), kick.parent_id);
+        assert_eq!(Some(kick.id), acoustic.parent_id);
+        assert_eq!(1, Tag::children(&conn, drums.id)?.len());
+
+        Tag::rename(&conn, &kick, &tag::Tag::from("percussion/kick"))?;
+        let percussion = Tag::by_name(&conn, "percussion")?.unwrap();
+        let acoustic = Tag::by_name(&conn, "percussion/kick/acoustic")?.unwrap();
+        assert_eq!(id, acoustic.id);
+        assert_eq!(Some(kick.id), acoustic.parent_id);
+        assert_eq!(
+            Some(percussion.id),
+            Tag::by_name(&conn, "percussion/kick")?.unwrap().parent_id
+        );
+        assert!(Tag::children(&conn, drums.id)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn delete_parent() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        rusqlite::vtab::array::load_module(&conn)?;
+        run_migrations(&conn)?;
+
+        let file_id = File::insert(
+            &conn,
+            &file_info::FileInfo::new(Location::try_from("LOCATION0")?, make_signature(0)),
+        )?;
+        let kick_id = Tag::upsert(&conn, &tag::Tag::from("drums/kick"))?;
+        FileTag::upsert(&conn, file_id, kick_id, None)?;
+        TagAlias::insert(&conn, "bd", kick_id)?;
+        let drums_id = Tag::by_name(&conn, "drums")?.unwrap().id;
+
+        // Parent cannot be deleted on its own while child is tagged on files
+        assert!(Tag::delete_by_id(&conn, drums_id).is_err());
+        assert!(Tag::delete_by_names(&conn, &vec!["drums"]).is_err());
+        assert_eq!(2, Tag::all(&conn, None)?.len());
+        assert_eq!(1, FileTag::count_by_tag_id(&conn, kick_id)?);
+
+        // Tag applied to files and with aliases is deleted along with them
+        assert_eq!(1, Tag::delete_by_id(&conn, kick_id)?);
+        assert_eq!(0, FileTag::count_by_tag_id(&conn, kick_id)?);
+        assert!(TagAlias::by_name(&conn, "bd")?.is_none());
+        assert_eq!(1, Tag::delete_by_id(&conn, drums_id)?);
+
+        // Parent and child may be deleted together
+        Tag::upsert(&conn, &tag::Tag::from("drums/snare"))?;
+        assert_eq!(
+            2,
+            Tag::delete_by_names(&conn, &vec!["drums", "drums/snare"])?.len()
+        );
+        assert!(Tag::all(&conn, None)?.is_empty());
         Ok(())
     }
 }
@@ -0,0 +1,36 @@
+use rusqlite::{params, Connection, NO_PARAMS};
+
+use crate::result::Result;
+
+pub fn run_migration(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "ALTER TABLE tags ADD COLUMN parent_id INTEGER REFERENCES tags(id) ON DELETE CASCADE;",
+    )?;
+
+    // Create ancestors of existing hierarchical tags and link each tag to its parent
+    let mut stmt = conn.prepare("SELECT name FROM tags")?;
+    let names = stmt
+        .query_map(NO_PARAMS, |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for name in names {
+        let mut parent_id: Option<i64> = None;
+        let mut prefix = String::new();
+        for segment in name.split('/') {
+            if !prefix.is_empty() {
+                prefix.push('/');
+            }
+            prefix.push_str(segment);
+            conn.execute(
+                "INSERT INTO tags (name, parent_id) VALUES (?1, ?2)
+                    ON CONFLICT(name) DO UPDATE SET parent_id = ?2",
+                params![prefix, parent_id],
+            )?;
+            parent_id = Some(conn.query_row(
+                "SELECT id FROM tags WHERE name = ?1",
+                params![prefix],
+                |row| row.get(0),
+            )?);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,9 @@
+use rusqlite::Connection;
+
+use crate::result::Result;
+
+pub fn run_migration(conn: &Connection) -> Result<()> {
+    // Column has no type affinity so that integer, decimal and text values keep their storage class
+    conn.execute_batch("ALTER TABLE file_tags ADD COLUMN value;")?;
+    Ok(())
+}
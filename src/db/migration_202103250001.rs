@@ -0,0 +1,15 @@
+use rusqlite::Connection;
+
+use crate::result::Result;
+
+pub fn run_migration(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE tag_aliases (
+            id          INTEGER PRIMARY KEY,
+            name        TEXT NOT NULL UNIQUE,
+            tag_id      INTEGER NOT NULL,
+            FOREIGN KEY(tag_id) REFERENCES tags(id) ON DELETE CASCADE
+        );",
+    )?;
+    Ok(())
+}
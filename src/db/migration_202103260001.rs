@@ -0,0 +1,12 @@
+use rusqlite::Connection;
+
+use crate::result::Result;
+
+pub fn run_migration(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "ALTER TABLE tags ADD COLUMN description TEXT;
+        ALTER TABLE tags ADD COLUMN color TEXT;
+        ALTER TABLE tags ADD COLUMN category TEXT;",
+    )?;
+    Ok(())
+}
@@ -0,0 +1,13 @@
+use rusqlite::Connection;
+
+use crate::result::Result;
+
+pub fn run_migration(conn: &Connection) -> Result<()> {
+    // Size and modification time are used to skip rehashing unchanged files and are
+    // populated the next time each file is scanned
+    conn.execute_batch(
+        "ALTER TABLE files ADD COLUMN size INTEGER;
+        ALTER TABLE files ADD COLUMN mtime INTEGER;",
+    )?;
+    Ok(())
+}
@@ -0,0 +1,13 @@
+use rusqlite::Connection;
+
+use crate::result::Result;
+
+pub fn run_migration(conn: &Connection) -> Result<()> {
+    // Files no longer found by scan are marked as missing instead of being deleted so
+    // that their tags can be recovered until they are explicitly pruned
+    conn.execute_batch(
+        "ALTER TABLE files ADD COLUMN missing INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE files ADD COLUMN last_seen INTEGER;",
+    )?;
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use rusqlite::Connection;
+
+use crate::result::Result;
+
+pub fn run_migration(conn: &Connection) -> Result<()> {
+    // Splits files into content identified by signature and instances of that content at
+    // each location so that copies of the same content can all be tracked and tagged
+    conn.execute_batch(
+        "PRAGMA foreign_keys = OFF;
+        BEGIN TRANSACTION;
+
+        CREATE TABLE contents (
+            id          INTEGER PRIMARY KEY,
+            signature   TEXT NOT NULL UNIQUE,
+            size        INTEGER
+        );
+        INSERT INTO contents (signature, size) SELECT signature, size FROM files;
+        INSERT OR IGNORE INTO contents (signature) SELECT signature FROM duplicate_files;
+
+        CREATE TABLE new_files (
+            id          INTEGER PRIMARY KEY,
+            location    TEXT NOT NULL UNIQUE,
+            content_id  INTEGER NOT NULL,
+            size        INTEGER,
+            mtime       INTEGER,
+            missing     INTEGER NOT NULL DEFAULT 0,
+            last_seen   INTEGER,
+            FOREIGN KEY(content_id) REFERENCES contents(id)
+        );
+        INSERT INTO new_files (id, location, content_id, size, mtime, missing, last_seen)
+            SELECT files.id, files.location, contents.id, files.size, files.mtime, files.missing, files.last_seen
+            FROM files INNER JOIN contents ON contents.signature = files.signature;
+        INSERT OR IGNORE INTO new_files (location, content_id)
+            SELECT duplicate_files.location, contents.id
+            FROM duplicate_files INNER JOIN contents ON contents.signature = duplicate_files.signature;
+        DROP TABLE files;
+        ALTER TABLE new_files RENAME TO files;
+        CREATE INDEX files_content_id ON files(content_id);
+
+        CREATE TABLE content_tags (
+            id          INTEGER PRIMARY KEY,
+            content_id  INTEGER NOT NULL,
+            tag_id      INTEGER NOT NULL,
+            value,
+            FOREIGN KEY(content_id) REFERENCES contents(id) ON DELETE CASCADE,
+            FOREIGN KEY(tag_id) REFERENCES tags(id) ON DELETE CASCADE,
+            UNIQUE(content_id, tag_id)
+        );
+
+        DROP TABLE duplicate_files;
+
+        PRAGMA foreign_key_check;
+        COMMIT;
+        PRAGMA foreign_keys = ON;",
+    )?;
+    Ok(())
+}
@@ -0,0 +1,16 @@
+use rusqlite::Connection;
+
+use crate::result::Result;
+
+pub fn run_migration(conn: &Connection) -> Result<()> {
+    // Signatures are prefixed with the algorithm used to compute them and all existing
+    // signatures were computed with SHA-256
+    conn.execute_batch(
+        "CREATE TABLE settings (
+            name    TEXT NOT NULL PRIMARY KEY,
+            value   TEXT NOT NULL
+        );
+        UPDATE contents SET signature = 'sha256:' || signature;",
+    )?;
+    Ok(())
+}
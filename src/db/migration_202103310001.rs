@@ -0,0 +1,12 @@
+use rusqlite::Connection;
+
+use crate::result::Result;
+
+pub fn run_migration(conn: &Connection) -> Result<()> {
+    // Quick signatures of existing files are filled in by next scan
+    conn.execute_batch(
+        "ALTER TABLE files ADD COLUMN quick_signature TEXT;
+        CREATE INDEX files_quick_signature ON files (quick_signature);",
+    )?;
+    Ok(())
+}
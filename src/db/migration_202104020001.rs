@@ -0,0 +1,8 @@
+use rusqlite::Connection;
+
+use crate::result::Result;
+
+pub fn run_migration(conn: &Connection) -> Result<()> {
+    conn.execute_batch("ALTER TABLE files ADD COLUMN fingerprint TEXT;")?;
+    Ok(())
+}
@@ -4,6 +4,17 @@ use std::collections::HashSet;
 use super::migration_202103210001;
 use super::migration_202103210002;
 use super::migration_202103220001;
+use super::migration_202103230001;
+use super::migration_202103240001;
+use super::migration_202103250001;
+use super::migration_202103260001;
+use super::migration_202103270001;
+use super::migration_202103280001;
+use super::migration_202103290001;
+use super::migration_202103300001;
+use super::migration_202103310001;
+use super::migration_202104010001;
+use super::migration_202104020001;
 use crate::result::Result;
 
 // Migrations will be run in the order defined in this array
@@ -11,6 +22,17 @@ static MIGRATIONS: &'static [(fn(&Connection) -> Result<()>, &'static str)] = &[
     (migration_202103210001::run_migration, "202103210001"),
     (migration_202103210002::run_migration, "202103210002"),
     (migration_202103220001::run_migration, "202103220001"),
+    (migration_202103230001::run_migration, "202103230001"),
+    (migration_202103240001::run_migration, "202103240001"),
+    (migration_202103250001::run_migration, "202103250001"),
+    (migration_202103260001::run_migration, "202103260001"),
+    (migration_202103270001::run_migration, "202103270001"),
+    (migration_202103280001::run_migration, "202103280001"),
+    (migration_202103290001::run_migration, "202103290001"),
+    (migration_202103300001::run_migration, "202103300001"),
+    (migration_202103310001::run_migration, "202103310001"),
+    (migration_202104010001::run_migration, "202104010001"),
+    (migration_202104020001::run_migration, "202104020001"),
 ];
 
 fn do_initial_migration(conn: &Connection) -> Result<()> {
@@ -1,9 +1,22 @@
+mod batch;
 mod dao;
 mod migration_202103210001;
 mod migration_202103210002;
 mod migration_202103220001;
+mod migration_202103230001;
+mod migration_202103240001;
+mod migration_202103250001;
+mod migration_202103260001;
+mod migration_202103270001;
+mod migration_202103280001;
+mod migration_202103290001;
+mod migration_202103300001;
+mod migration_202103310001;
+mod migration_202104010001;
+mod migration_202104020001;
 mod migrations;
 mod util;
 
-pub use self::dao::{DuplicateFile, File, FileTag, Tag};
+pub use self::batch::Batch;
+pub use self::dao::{Content, ContentTag, File, FileTag, Setting, Tag, TagAlias, UpsertOutcome};
 pub use self::migrations::run_migrations;
@@ -0,0 +1,46 @@
+use std::path::{Path, PathBuf};
+
+use crate::result::{user_error_result, Error, Result};
+
+// File that could not be processed and reason why
+pub struct Failure {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+impl Failure {
+    pub fn new(path: &Path, error: Error) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            reason: match error {
+                Error::User(message) => message,
+                Error::Internal(facility, message) => format!("{} ({})", message, facility),
+            },
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "path": self.path.display().to_string(),
+            "reason": self.reason,
+        })
+    }
+}
+
+pub fn show_failures(failures: &Vec<Failure>) {
+    if !failures.is_empty() {
+        println!("Failed to process {} files:", failures.len());
+        for failure in failures {
+            println!("  {}: {}", failure.path.display(), failure.reason);
+        }
+    }
+}
+
+// Reports failures through exit status once everything else has been processed
+pub fn check_failures(failures: &Vec<Failure>) -> Result<()> {
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        user_error_result(format!("Failed to process {} files", failures.len()))
+    }
+}
@@ -1,12 +1,37 @@
+use std::fs::Metadata;
 use std::path::Path;
+use std::time::UNIX_EPOCH;
 
 use crate::location::Location;
 use crate::result::Result;
-use crate::signature::Signature;
+use crate::signature::{Algorithm, QuickSignature, Signature};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FileStat {
+    pub size: i64,
+    // Modification time in nanoseconds since Unix epoch
+    pub mtime: i64,
+}
+
+impl FileStat {
+    pub fn from_metadata(metadata: &Metadata) -> Result<Self> {
+        let mtime = metadata.modified()?.duration_since(UNIX_EPOCH)?;
+        Ok(Self {
+            size: metadata.len() as i64,
+            mtime: mtime.as_secs() as i64 * 1_000_000_000 + mtime.subsec_nanos() as i64,
+        })
+    }
+
+    pub fn from_path(path: &Path) -> Result<Self> {
+        Self::from_metadata(&path.metadata()?)
+    }
+}
 
 pub struct FileInfo {
     pub location: Location,
     pub signature: Signature,
+    pub stat: Option<FileStat>,
+    pub quick_signature: Option<QuickSignature>,
 }
 
 impl FileInfo {
@@ -14,13 +39,17 @@ impl FileInfo {
         Self {
             location: location,
             signature: signature,
+            stat: None,
+            quick_signature: None,
         }
     }
 
-    pub fn from_file(start_dir: &Path, path: &Path) -> Result<Self> {
+    pub fn from_file(start_dir: &Path, path: &Path, algorithm: Algorithm) -> Result<Self> {
         Ok(Self {
             location: Location::from_path(&start_dir, &path)?,
-            signature: Signature::from_file(path)?,
+            signature: Signature::from_file(path, algorithm)?,
+            stat: Some(FileStat::from_path(path)?),
+            quick_signature: Some(QuickSignature::from_file(path)?),
         })
     }
 }
@@ -0,0 +1,336 @@
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::audio;
+use crate::result::{internal_error_result, Error, Result};
+
+// Number of equal-length segments that audio is divided into
+const SEGMENT_COUNT: usize = 32;
+
+// Length of window used to find start and end of audio
+const WINDOW_MS: u64 = 5;
+
+// Windows quieter than this RMS level (-60 dBFS) are treated as silence
+const SILENCE_LEVEL: f64 = 0.001;
+
+// Quietest level distinguished by fingerprint in dBFS
+const FLOOR_DB: f64 = -90.0;
+
+// Highest zero crossing rate distinguished by fingerprint in crossings per sample
+const MAX_ZERO_CROSSING_RATE: f64 = 0.5;
+
+// Coarse description of how loudness and brightness of decoded audio change over time:
+// unlike signature it is unaffected by bit depth and by leading or trailing silence
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fingerprint {
+    duration_ms: u64,
+    // Loudness and zero crossing rate of each segment
+    features: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Window {
+    frames: u64,
+    sum_squares: f64,
+    crossings: u64,
+}
+
+impl Window {
+    fn rms(&self) -> f64 {
+        if self.frames == 0 {
+            0.0
+        } else {
+            (self.sum_squares / self.frames as f64).sqrt()
+        }
+    }
+
+    fn merge(windows: &[Window]) -> Window {
+        windows.iter().fold(Window::default(), |acc, x| Window {
+            frames: acc.frames + x.frames,
+            sum_squares: acc.sum_squares + x.sum_squares,
+            crossings: acc.crossings + x.crossings,
+        })
+    }
+}
+
+impl Fingerprint {
+    // Stored for files without audio to fingerprint so that they are not decoded again:
+    // it is not similar to anything
+    pub fn empty() -> Self {
+        Self {
+            duration_ms: 0,
+            features: Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+    }
+
+    // Decodes WAV or AIFF file: returns None for other formats and for silent files
+    pub fn from_file(path: &Path) -> Result<Option<Self>> {
+        let mut f = File::open(path)?;
+        let (format, range) = match audio::pcm_range(&mut f)? {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        f.seek(SeekFrom::Start(range.start))?;
+        let mut reader = BufReader::new(f.take(range.end - range.start));
+
+        let window_frames = (format.sample_rate as u64 * WINDOW_MS / 1000).max(1);
+        let sample_size = format.sample_size();
+        let mut frame = vec![0u8; format.frame_size()];
+        let mut windows = Vec::new();
+        let mut window = Window::default();
+        let mut previous = 0f32;
+        loop {
+            match reader.read_exact(&mut frame) {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            }
+
+            // Channels are mixed down to mono
+            let sample = frame
+                .chunks(sample_size)
+                .map(|x| format.decode_sample(x))
+                .sum::<f32>()
+                / format.channels as f32;
+            window.sum_squares += (sample * sample) as f64;
+            if (sample >= 0.0) != (previous >= 0.0) {
+                window.crossings += 1;
+            }
+            previous = sample;
+            window.frames += 1;
+            if window.frames == window_frames {
+                windows.push(window);
+                window = Window::default();
+            }
+        }
+        if window.frames > 0 {
+            windows.push(window);
+        }
+
+        Ok(Self::from_windows(&windows, format.sample_rate))
+    }
+
+    fn from_windows(windows: &[Window], sample_rate: u32) -> Option<Self> {
+        let first = windows.iter().position(|x| x.rms() >= SILENCE_LEVEL)?;
+        let last = windows.iter().rposition(|x| x.rms() >= SILENCE_LEVEL)?;
+        let windows = &windows[first..=last];
+
+        let mut features = Vec::with_capacity(SEGMENT_COUNT * 2);
+        for i in 0..SEGMENT_COUNT {
+            let start = i * windows.len() / SEGMENT_COUNT;
+            let end = ((i + 1) * windows.len() / SEGMENT_COUNT).max(start + 1);
+            let segment = Window::merge(&windows[start..end]);
+
+            let rms = segment.rms();
+            let db = if rms > 0.0 {
+                (20.0 * rms.log10()).clamp(FLOOR_DB, 0.0)
+            } else {
+                FLOOR_DB
+            };
+            features.push(((db - FLOOR_DB) / -FLOOR_DB * 255.0).round() as u8);
+
+            // Zero crossings of near-silent segments are dominated by noise and dither
+            let rate = if rms >= SILENCE_LEVEL {
+                segment.crossings as f64 / segment.frames as f64
+            } else {
+                0.0
+            };
+            // Square root spreads out low rates typical of musical material
+            let rate = (rate / MAX_ZERO_CROSSING_RATE).min(1.0).sqrt();
+            features.push((rate * 255.0).round() as u8);
+        }
+
+        Some(Self {
+            duration_ms: Window::merge(windows).frames * 1000 / sample_rate.max(1) as u64,
+            features: features,
+        })
+    }
+
+    // Returns value between 0 and 1 where 1 means that audio is indistinguishable
+    pub fn similarity(&self, other: &Self) -> f64 {
+        if self.features.len() != other.features.len() || self.features.is_empty() {
+            return 0.0;
+        }
+        let difference = self
+            .features
+            .iter()
+            .zip(&other.features)
+            .map(|(a, b)| (*a as i32 - *b as i32).abs() as f64)
+            .sum::<f64>()
+            / (self.features.len() as f64 * 255.0);
+        (1.0 - difference) * self.duration_ratio(other)
+    }
+
+    // Ratio of shorter to longer duration: similarity cannot exceed this
+    pub fn duration_ratio(&self, other: &Self) -> f64 {
+        let shorter = self.duration_ms.min(other.duration_ms);
+        let longer = self.duration_ms.max(other.duration_ms);
+        if longer == 0 {
+            1.0
+        } else {
+            shorter as f64 / longer as f64
+        }
+    }
+
+    pub fn duration_ms(&self) -> u64 {
+        self.duration_ms
+    }
+}
+
+impl std::fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:", self.duration_ms)?;
+        for x in &self.features {
+            write!(f, "{:02x}", x)?;
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<&str> for Fingerprint {
+    type Error = Error;
+
+    fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+        let mut parts = value.splitn(2, ':');
+        let duration_ms = parts.next().and_then(|x| x.parse::<u64>().ok());
+        let hex = parts.next().unwrap_or("");
+        let features = (0..hex.len())
+            .step_by(2)
+            .map(|i| {
+                hex.get(i..i + 2)
+                    .and_then(|x| u8::from_str_radix(x, 16).ok())
+            })
+            .collect::<Option<Vec<_>>>();
+        match (duration_ms, features) {
+            (Some(duration_ms), Some(features)) if features.len() == SEGMENT_COUNT * 2 => {
+                Ok(Self {
+                    duration_ms: duration_ms,
+                    features: features,
+                })
+            }
+            (Some(0), Some(features)) if features.is_empty() => Ok(Self::empty()),
+            _ => internal_error_result("Fingerprint", format!("Invalid fingerprint \"{}\"", value)),
+        }
+    }
+}
+
+impl FromSql for Fingerprint {
+    fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+        value
+            .as_str()
+            .and_then(|s| Self::try_from(s).map_err(|_| FromSqlError::InvalidType))
+    }
+}
+
+impl ToSql for Fingerprint {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+    use crate::test_util::TempPath;
+
+    const SAMPLE_RATE: u32 = 44100;
+
+    // Writes mono WAV file with given bit depth
+    fn write_wav(name: &str, samples: &[f64], bits: u16) -> Result<TempPath> {
+        let bytes_per_sample = (bits / 8) as usize;
+        let mut data = Vec::new();
+        for sample in samples {
+            let value = (sample * ((1i64 << (bits - 1)) - 1) as f64).round() as i32;
+            data.extend(&value.to_le_bytes()[..bytes_per_sample]);
+        }
+        let mut bytes = b"RIFF\0\0\0\0WAVEfmt \x10\0\0\0".to_vec();
+        bytes.extend(&1u16.to_le_bytes());
+        bytes.extend(&1u16.to_le_bytes());
+        bytes.extend(&SAMPLE_RATE.to_le_bytes());
+        bytes.extend(&(SAMPLE_RATE * bytes_per_sample as u32).to_le_bytes());
+        bytes.extend(&(bytes_per_sample as u16).to_le_bytes());
+        bytes.extend(&bits.to_le_bytes());
+        bytes.extend(b"data");
+        bytes.extend(&(data.len() as u32).to_le_bytes());
+        bytes.extend(&data);
+
+        let temp = TempPath::new(&format!("fingerprint-{}.wav", name));
+        std::fs::write(temp.path(), &bytes)?;
+        Ok(temp)
+    }
+
+    // Decaying tone lasting given number of milliseconds
+    fn tone(frequency: f64, ms: usize) -> Vec<f64> {
+        let frames = SAMPLE_RATE as usize * ms / 1000;
+        (0..frames)
+            .map(|i| {
+                let t = i as f64 / SAMPLE_RATE as f64;
+                0.8 * (-t * 4.0).exp() * (2.0 * PI * frequency * t).sin()
+            })
+            .collect()
+    }
+
+    fn fingerprint(name: &str, samples: &[f64], bits: u16) -> Result<Fingerprint> {
+        let temp = write_wav(name, samples, bits)?;
+        Ok(Fingerprint::from_file(temp.path())?.unwrap())
+    }
+
+    #[test]
+    fn test_similarity() -> Result<()> {
+        let original = tone(440.0, 500);
+        let mut padded = original.clone();
+        padded.extend(vec![0.0; SAMPLE_RATE as usize * 20 / 1000]);
+
+        let fingerprint16 = fingerprint("16", &original, 16)?;
+        let fingerprint24 = fingerprint("24", &original, 24)?;
+        let fingerprint_padded = fingerprint("padded", &padded, 16)?;
+        let fingerprint_other = fingerprint("other", &tone(3000.0, 500), 16)?;
+        let fingerprint_short = fingerprint("short", &tone(440.0, 250), 16)?;
+
+        assert_eq!(500, fingerprint16.duration_ms());
+        assert!(fingerprint16.similarity(&fingerprint24) > 0.99);
+        assert!(fingerprint16.similarity(&fingerprint_padded) > 0.99);
+        assert!(fingerprint16.similarity(&fingerprint_other) < 0.9);
+        assert!(fingerprint16.similarity(&fingerprint_short) < 0.6);
+        Ok(())
+    }
+
+    #[test]
+    fn test_silence() -> Result<()> {
+        let temp = write_wav("silence", &vec![0.0; 1000], 16)?;
+        assert_eq!(None, Fingerprint::from_file(temp.path())?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_not_audio() -> Result<()> {
+        let temp = TempPath::new("fingerprint");
+        std::fs::write(temp.path(), b"abc")?;
+        assert_eq!(None, Fingerprint::from_file(temp.path())?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_try_from() -> Result<()> {
+        let fingerprint = fingerprint("try-from", &tone(440.0, 100), 16)?;
+        let value = fingerprint.to_string();
+        assert!(value.starts_with("100:"));
+        assert_eq!(fingerprint, Fingerprint::try_from(value.as_str())?);
+        assert!(Fingerprint::try_from("100:abc").is_err());
+        assert!(Fingerprint::try_from("100:").is_err());
+        assert_eq!("0:", Fingerprint::empty().to_string());
+        assert!(Fingerprint::try_from("0:")?.is_empty());
+        assert_eq!(0.0, fingerprint.similarity(&Fingerprint::empty()));
+        assert!(Fingerprint::try_from("FINGERPRINT").is_err());
+        Ok(())
+    }
+}
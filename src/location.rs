@@ -2,22 +2,22 @@ use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
 use std::convert::TryFrom;
 use std::path::{Path, PathBuf};
 
-use crate::result::{Error, Result};
+use crate::result::{internal_error_result, Error, Result};
 
-#[derive(Debug, Hash, Eq, PartialEq)]
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
 pub struct Location(String);
 
 impl Location {
-    #[cfg(windows)]
     const LOCATION_SEPARATOR: &'static str = "/";
 
     #[cfg(windows)]
     const OS_SEPARATOR: &'static str = "\\";
 
     pub fn from_path(base_dir: impl AsRef<Path>, path: impl AsRef<Path>) -> Result<Self> {
-        Ok(Self(Self::from_os_path_string(
-            path.as_ref().strip_prefix(base_dir)?.to_str()?,
-        )))
+        match path.as_ref().strip_prefix(base_dir)?.to_str() {
+            Some(s) => Ok(Self(Self::from_os_path_string(s))),
+            None => internal_error_result("Path", "File name is not valid UTF-8"),
+        }
     }
 
     pub fn to_path(&self, base_dir: impl AsRef<Path>) -> PathBuf {
@@ -28,6 +28,15 @@ impl Location {
         &self.0
     }
 
+    // True if other location is this location or lies underneath it, with empty
+    // location corresponding to the project directory itself
+    pub fn contains(&self, other: &Location) -> bool {
+        self.0.is_empty()
+            || other.0 == self.0
+            || (other.0.starts_with(&self.0)
+                && other.0[self.0.len()..].starts_with(Self::LOCATION_SEPARATOR))
+    }
+
     pub fn into_string(self) -> String {
         self.0
     }
@@ -123,6 +132,17 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_contains() -> Result<()> {
+        let location = Location::try_from("aaa/bbb")?;
+        assert!(location.contains(&Location::try_from("aaa/bbb")?));
+        assert!(location.contains(&Location::try_from("aaa/bbb/ccc")?));
+        assert!(!location.contains(&Location::try_from("aaa/bbbccc")?));
+        assert!(!location.contains(&Location::try_from("aaa")?));
+        assert!(Location::try_from("")?.contains(&location));
+        Ok(())
+    }
+
     #[test]
     fn test_try_from() -> Result<()> {
         let location = Location::try_from("LOCATION")?;
@@ -1,21 +1,28 @@
 #![feature(try_trait)]
 #![allow(dead_code)]
 
-#[macro_use]
-extern crate lazy_static;
-
 mod action;
+mod audio;
 mod cli;
+mod color;
 mod db;
+mod failure;
 mod file_info;
+mod fingerprint;
 mod like;
 mod location;
 mod media_path_checker;
+mod progress;
 mod project;
+mod query;
 mod result;
 mod sample_visitor;
+mod scanner;
 mod signature;
 mod tag;
+#[cfg(test)]
+mod test_util;
+mod util;
 
 use absolute_path::absolute_path;
 use clap::ArgMatches;
@@ -26,13 +33,18 @@ use std::path::{Path, PathBuf};
 use std::process::exit;
 
 use crate::action::{
-    do_check_database, do_check_file_system, do_default, do_delete_tag, do_list_files,
-    do_list_tags, do_scan, do_search, do_show_file, do_tag,
+    do_add_alias, do_check_database, do_check_file_system, do_default, do_delete_tag, do_filters,
+    do_list_aliases, do_list_files, do_list_tags, do_merge_tags, do_prune, do_rehash,
+    do_remove_alias, do_rename_tag, do_scan, do_search, do_show_file, do_similar, do_tag,
+    do_tag_info, do_untag, do_verify, TagInfoUpdate,
 };
 use crate::cli::{arg, command, make_app};
 use crate::like::Like;
 use crate::project::Project;
+use crate::query::Expr;
 use crate::result::{user_error_result, Error, Result};
+use crate::scanner::ScanOptions;
+use crate::signature::Algorithm;
 use crate::tag::Tag;
 
 #[cfg(windows)]
@@ -76,25 +88,105 @@ fn main_inner() -> Result<()> {
         (command::CHECK_DATABASE, _submatches) => do_check_database(&project),
         (command::CHECK_FILE_SYSTEM, _submatches) => do_check_file_system(&project),
         (command::DEFAULT, _submatches) => do_default(&project),
-        (command::DELETE_TAG, Some(submatches)) => do_delete_tag(&project, &get_tags(submatches)?),
-        (command::SCAN, _submatches) => do_scan(&project),
-        (command::SEARCH, Some(submatches)) => do_search(&project, &get_tags(submatches)?),
+        (command::DELETE_TAG, Some(submatches)) => do_delete_tag(
+            &project,
+            &get_tags(submatches)?,
+            submatches.is_present(arg::DRY_RUN),
+        ),
+        (command::SCAN, Some(submatches)) => do_scan(
+            &project,
+            &get_optional_paths(&working_dir, submatches)?,
+            &ScanOptions {
+                jobs: get_jobs(submatches)?,
+                full: submatches.is_present(arg::FULL),
+            },
+            submatches.is_present(arg::JSON),
+            submatches.is_present(arg::DRY_RUN),
+        ),
+        (command::SEARCH, Some(submatches)) => do_search(&project, &get_query(submatches)?),
         (command::TAG, Some(submatches)) => do_tag(
             &project,
             &get_tags(submatches)?,
             &get_paths(&working_dir, submatches)?,
+            submatches.is_present(arg::CONTENT),
+            submatches.is_present(arg::DRY_RUN),
+        ),
+        (command::UNTAG, Some(submatches)) => do_untag(
+            &project,
+            &get_tags(submatches)?,
+            &get_paths(&working_dir, submatches)?,
+            submatches.is_present(arg::CONTENT),
+            submatches.is_present(arg::DELETE_UNUSED_TAGS),
         ),
 
         // New commands
         (command::SHOW_FILE, Some(submatches)) => {
             do_show_file(&project, &get_path(&working_dir, submatches)?)
         }
-        (command::LIST_FILES, Some(submatches)) => {
-            do_list_files(&project, get_optional_like(submatches)?)
+        (command::LIST_FILES, Some(submatches)) => do_list_files(
+            &project,
+            get_optional_like(submatches)?,
+            get_size(submatches, arg::MIN_SIZE)?,
+            get_size(submatches, arg::MAX_SIZE)?,
+        ),
+        (command::LIST_TAGS, Some(submatches)) => do_list_tags(
+            &project,
+            get_optional_like(submatches)?,
+            submatches.is_present(arg::TREE),
+        ),
+        (command::MERGE_TAGS, Some(submatches)) => {
+            let (source_tags, target_tag) = get_merge_tags(submatches)?;
+            do_merge_tags(&project, &source_tags, &target_tag)
         }
-        (command::LIST_TAGS, Some(submatches)) => {
-            do_list_tags(&project, get_optional_like(submatches)?)
+        (command::RENAME_TAG, Some(submatches)) => do_rename_tag(
+            &project,
+            &Tag::from(submatches.value_of(arg::OLD_TAG)?),
+            &Tag::from(submatches.value_of(arg::NEW_TAG)?),
+        ),
+        (command::ADD_ALIAS, Some(submatches)) => do_add_alias(
+            &project,
+            &Tag::from(submatches.value_of(arg::ALIAS)?),
+            &Tag::from(submatches.value_of(arg::TAG)?),
+        ),
+        (command::REMOVE_ALIAS, Some(submatches)) => do_remove_alias(
+            &project,
+            &submatches
+                .values_of(arg::ALIASES)?
+                .map(|x| Tag::from(x))
+                .collect(),
+        ),
+        (command::LIST_ALIASES, Some(submatches)) => {
+            do_list_aliases(&project, get_optional_like(submatches)?)
         }
+        (command::TAG_INFO, Some(submatches)) => do_tag_info(
+            &project,
+            &Tag::from(submatches.value_of(arg::TAG)?),
+            &TagInfoUpdate {
+                description: submatches.value_of(arg::DESCRIPTION),
+                color: submatches.value_of(arg::COLOR),
+                category: submatches.value_of(arg::CATEGORY),
+            },
+        ),
+        (command::PRUNE, Some(submatches)) => do_prune(
+            &project,
+            &get_optional_paths(&working_dir, submatches)?,
+            get_older_than(submatches)?,
+        ),
+        (command::REHASH, Some(submatches)) => do_rehash(
+            &project,
+            Algorithm::try_from(submatches.value_of(arg::ALGORITHM)?)?,
+        ),
+        (command::VERIFY, Some(submatches)) => do_verify(
+            &project,
+            get_days(submatches)?,
+            submatches.is_present(arg::RESUME),
+        ),
+        (command::SIMILAR, Some(submatches)) => do_similar(&project, get_threshold(submatches)?),
+        (command::FILTERS, Some(submatches)) => do_filters(
+            &project,
+            submatches.value_of(arg::INCLUDE),
+            submatches.value_of(arg::EXCLUDE),
+        ),
 
         // Catch-all
         (c, _submatches) => panic!("Subcommand \"{}\" not implemented", c),
@@ -108,6 +200,83 @@ fn get_tags<'a>(submatches: &'a ArgMatches) -> Result<Vec<Tag<'a>>> {
         .collect())
 }
 
+fn get_merge_tags<'a>(submatches: &'a ArgMatches) -> Result<(Vec<Tag<'a>>, Tag<'a>)> {
+    let values = submatches.values_of(arg::TAGS)?.collect::<Vec<_>>();
+    match values.as_slice() {
+        [sources @ .., into, target]
+            if into.eq_ignore_ascii_case("into") && !sources.is_empty() =>
+        {
+            Ok((
+                sources.iter().map(|x| Tag::from(x)).collect(),
+                Tag::from(target),
+            ))
+        }
+        _ => user_error_result("Expected one or more source tags followed by INTO and target tag"),
+    }
+}
+
+fn get_query(submatches: &ArgMatches) -> Result<Expr> {
+    let tag_expr = match submatches.values_of(arg::TAG) {
+        Some(values) => Expr::all_of(&values.map(|x| Tag::from(x)).collect()),
+        None => None,
+    };
+    let query_expr = match submatches.values_of(arg::QUERY) {
+        Some(values) => Some(Expr::parse(&values.collect::<Vec<_>>().join(" "))?),
+        None => None,
+    };
+    match (tag_expr, query_expr) {
+        (Some(lhs), Some(rhs)) => Ok(Expr::And(Box::new(lhs), Box::new(rhs))),
+        (Some(e), None) | (None, Some(e)) => Ok(e),
+        (None, None) => user_error_result("No tags or query specified"),
+    }
+}
+
+fn get_jobs(submatches: &ArgMatches) -> Result<usize> {
+    match submatches.value_of(arg::JOBS) {
+        Some(s) => match s.parse::<usize>() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => user_error_result(format!("Invalid number of jobs \"{}\"", s)),
+        },
+        None => Ok(num_cpus::get()),
+    }
+}
+
+fn get_older_than(submatches: &ArgMatches) -> Result<i64> {
+    let s = submatches.value_of(arg::OLDER_THAN)?;
+    match s.parse::<i64>() {
+        Ok(n) if n >= 0 => Ok(n),
+        _ => user_error_result(format!("Invalid number of days \"{}\"", s)),
+    }
+}
+
+fn get_days(submatches: &ArgMatches) -> Result<Option<i64>> {
+    match submatches.value_of(arg::DAYS) {
+        Some(s) => match s.parse::<i64>() {
+            Ok(n) if n >= 0 => Ok(Some(n)),
+            _ => user_error_result(format!("Invalid number of days \"{}\"", s)),
+        },
+        None => Ok(None),
+    }
+}
+
+fn get_size(submatches: &ArgMatches, name: &str) -> Result<Option<i64>> {
+    match submatches.value_of(name) {
+        Some(s) => match s.parse::<i64>() {
+            Ok(n) if n >= 0 => Ok(Some(n)),
+            _ => user_error_result(format!("Invalid size \"{}\"", s)),
+        },
+        None => Ok(None),
+    }
+}
+
+fn get_threshold(submatches: &ArgMatches) -> Result<f64> {
+    let s = submatches.value_of(arg::THRESHOLD)?;
+    match s.parse::<f64>() {
+        Ok(x) if (0.0..=1.0).contains(&x) => Ok(x),
+        _ => user_error_result(format!("Invalid similarity threshold \"{}\"", s)),
+    }
+}
+
 fn get_path(working_dir: &impl AsRef<Path>, submatches: &ArgMatches) -> Result<PathBuf> {
     let p = submatches.value_of(arg::PATH)?;
     Ok(absolute_path(&working_dir, p)?)
@@ -120,6 +289,18 @@ fn get_paths(working_dir: &impl AsRef<Path>, submatches: &ArgMatches) -> Result<
         .collect::<std::io::Result<_>>()?)
 }
 
+fn get_optional_paths(
+    working_dir: &impl AsRef<Path>,
+    submatches: &ArgMatches,
+) -> Result<Vec<PathBuf>> {
+    match submatches.values_of(arg::PATHS) {
+        Some(values) => Ok(values
+            .map(|x| absolute_path(&working_dir, x))
+            .collect::<std::io::Result<_>>()?),
+        None => Ok(Vec::new()),
+    }
+}
+
 fn get_optional_like<'a>(submatches: &'a ArgMatches) -> Result<Option<Like>> {
     match submatches.value_of(arg::LIKE) {
         Some(s) => Like::try_from(s).map(|x| Some(x)),
@@ -1,22 +1,34 @@
 use regex::Regex;
 use std::path::{Path, MAIN_SEPARATOR};
 
-use crate::result::Result;
+use crate::result::{internal_error_result, Result};
 use crate::sample_visitor::PathChecker;
 
-pub struct MediaPathChecker {}
+// Matches files with any of given extensions that are not inside any of given directories
+#[derive(Clone)]
+pub struct MediaPathChecker {
+    include_regexes: Vec<Regex>,
+    exclude_regexes: Vec<Regex>,
+}
 
 impl MediaPathChecker {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(extensions: &[&str], excluded_directories: &[&str]) -> Result<Self> {
+        Ok(Self {
+            include_regexes: create_include_regexes(extensions)?,
+            exclude_regexes: create_exclude_regexes(excluded_directories)?,
+        })
     }
 }
 
-const MEDIA_FILE_EXTENSIONS: [&'static str; 8] =
+// Used by projects that have not configured their own
+pub const DEFAULT_MEDIA_FILE_EXTENSIONS: [&'static str; 8] =
     ["aiff", "au", "mid", "m4a", "mp3", "snd", "wav", "wma"];
-const EXCLUDED_DIRECTORIES: [&'static str; 1] = ["__MACOSX"];
+pub const DEFAULT_EXCLUDED_DIRECTORIES: [&'static str; 1] = ["__MACOSX"];
 
 fn create_include_regexes(extensions: &[&str]) -> Result<Vec<Regex>> {
+    if extensions.is_empty() {
+        return Ok(Vec::new());
+    }
     let pattern = format!(
         r"(?i)^.+\.({})$",
         extensions
@@ -28,9 +40,9 @@ fn create_include_regexes(extensions: &[&str]) -> Result<Vec<Regex>> {
     Ok(vec![Regex::new(&pattern)?])
 }
 
-fn create_exclude_regexes() -> Result<Vec<Regex>> {
+fn create_exclude_regexes(directories: &[&str]) -> Result<Vec<Regex>> {
     let escaped_separator = regex::escape(&MAIN_SEPARATOR.to_string());
-    Ok(EXCLUDED_DIRECTORIES
+    Ok(directories
         .iter()
         .map(|x| {
             Regex::new(&format!(
@@ -43,22 +55,18 @@ fn create_exclude_regexes() -> Result<Vec<Regex>> {
         .collect::<std::result::Result<Vec<Regex>, regex::Error>>()?)
 }
 
-lazy_static! {
-    static ref INCLUDE_REGEXES: Vec<Regex> =
-        create_include_regexes(&MEDIA_FILE_EXTENSIONS).expect("Static initialization failure");
-    static ref EXCLUDE_REGEXES: Vec<Regex> =
-        create_exclude_regexes().expect("Static initialization failure");
-}
-
 fn matches_any_of(regexes: &Vec<Regex>, s: &str) -> bool {
     regexes.iter().any(|x| x.is_match(s))
 }
 
 impl PathChecker for MediaPathChecker {
     fn matches(&self, path: &impl AsRef<Path>) -> Result<bool> {
-        let path_str = path.as_ref().to_str()?;
-        Ok(matches_any_of(&INCLUDE_REGEXES, path_str)
-            && !matches_any_of(&EXCLUDE_REGEXES, path_str))
+        let path_str = match path.as_ref().to_str() {
+            Some(x) => x,
+            None => return internal_error_result("Path", "File name is not valid UTF-8"),
+        };
+        Ok(matches_any_of(&self.include_regexes, path_str)
+            && !matches_any_of(&self.exclude_regexes, path_str))
     }
 }
 
@@ -80,4 +88,21 @@ mod tests {
         assert!(!matches_any_of(&e, "p/q/r"));
         Ok(())
     }
+
+    #[test]
+    fn test_matches() -> Result<()> {
+        let checker = MediaPathChecker::new(&["wav", "flac", "rx2"], &["__MACOSX", "Backup"])?;
+        let path = |s: &str| s.replace('/', &MAIN_SEPARATOR.to_string());
+        assert!(checker.matches(&path("p/q/r.wav"))?);
+        assert!(checker.matches(&path("p/q/r.FLAC"))?);
+        assert!(checker.matches(&path("p/q/r.rx2"))?);
+        assert!(!checker.matches(&path("p/q/r.mp3"))?);
+        assert!(!checker.matches(&path("p/__MACOSX/r.wav"))?);
+        assert!(!checker.matches(&path("p/Backup/q/r.wav"))?);
+        assert!(checker.matches(&path("p/Backups/r.wav"))?);
+
+        let checker = MediaPathChecker::new(&[], &[])?;
+        assert!(!checker.matches(&path("p/q/r.wav"))?);
+        Ok(())
+    }
 }
@@ -0,0 +1,151 @@
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+// Minimum time between redraws of progress line
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+// Single-line progress indicator written to standard error when it is a terminal
+pub struct Progress {
+    enabled: bool,
+    total_files: u64,
+    total_bytes: u64,
+    files: u64,
+    bytes: u64,
+    bytes_hashed: u64,
+    start: Instant,
+    last_draw: Option<Instant>,
+}
+
+impl Progress {
+    pub fn is_supported() -> bool {
+        atty::is(atty::Stream::Stderr)
+    }
+
+    pub fn new(total_files: u64, total_bytes: u64) -> Self {
+        Self {
+            enabled: Self::is_supported(),
+            total_files: total_files,
+            total_bytes: total_bytes,
+            files: 0,
+            bytes: 0,
+            bytes_hashed: 0,
+            start: Instant::now(),
+            last_draw: None,
+        }
+    }
+
+    pub fn disabled() -> Self {
+        let mut progress = Self::new(0, 0);
+        progress.enabled = false;
+        progress
+    }
+
+    // Records visited file of given size and number of bytes actually hashed
+    pub fn update(&mut self, size: u64, hashed: u64) {
+        self.files += 1;
+        self.bytes += size;
+        self.bytes_hashed += hashed;
+        if !self.enabled {
+            return;
+        }
+
+        let now = Instant::now();
+        if let Some(last_draw) = self.last_draw {
+            if now.duration_since(last_draw) < REDRAW_INTERVAL {
+                return;
+            }
+        }
+        self.last_draw = Some(now);
+        eprint!("\r{}\x1b[K", self.format(now.duration_since(self.start)));
+        let _ = io::stderr().flush();
+    }
+
+    pub fn finish(&self) {
+        if self.enabled && self.last_draw.is_some() {
+            eprint!("\r\x1b[K");
+            let _ = io::stderr().flush();
+        }
+    }
+
+    fn format(&self, elapsed: Duration) -> String {
+        let secs = elapsed.as_secs_f64();
+        let throughput = if secs > 0f64 {
+            (self.bytes_hashed as f64 / secs) as u64
+        } else {
+            0
+        };
+        let eta = match estimate_remaining(self.bytes, self.total_bytes, secs) {
+            Some(x) => format_duration(x),
+            None => String::from("--:--"),
+        };
+        format!(
+            "{}/{} files, {} hashed, {}/s, ETA {}",
+            self.files,
+            self.total_files,
+            format_bytes(self.bytes_hashed),
+            format_bytes(throughput),
+            eta
+        )
+    }
+}
+
+// Estimates seconds remaining from fraction of total bytes processed so far
+fn estimate_remaining(bytes: u64, total_bytes: u64, elapsed_secs: f64) -> Option<u64> {
+    if bytes == 0 || total_bytes < bytes {
+        return None;
+    }
+    let remaining = (total_bytes - bytes) as f64 * elapsed_secs / bytes as f64;
+    Some(remaining.round() as u64)
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024f64 && unit < UNITS.len() - 1 {
+        value /= 1024f64;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+fn format_duration(secs: u64) -> String {
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{:02}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!("0 B", format_bytes(0));
+        assert_eq!("1023 B", format_bytes(1023));
+        assert_eq!("1.0 KiB", format_bytes(1024));
+        assert_eq!("1.5 MiB", format_bytes(1024 * 1024 * 3 / 2));
+        assert_eq!("2.0 GiB", format_bytes(2 * 1024 * 1024 * 1024));
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!("00:00", format_duration(0));
+        assert_eq!("01:05", format_duration(65));
+        assert_eq!("1:01:01", format_duration(3661));
+    }
+
+    #[test]
+    fn test_estimate_remaining() {
+        assert_eq!(None, estimate_remaining(0, 100, 1f64));
+        assert_eq!(Some(30), estimate_remaining(25, 100, 10f64));
+        assert_eq!(Some(0), estimate_remaining(100, 100, 10f64));
+        assert_eq!(None, estimate_remaining(200, 100, 10f64));
+    }
+}
@@ -1,15 +1,23 @@
 use rusqlite::Connection;
+use std::convert::TryFrom;
 use std::path::{Path, PathBuf};
 
-use crate::db::run_migrations;
-use crate::media_path_checker::MediaPathChecker;
+use crate::db::{run_migrations, Setting};
+use crate::media_path_checker::{
+    MediaPathChecker, DEFAULT_EXCLUDED_DIRECTORIES, DEFAULT_MEDIA_FILE_EXTENSIONS,
+};
 use crate::result::Result;
-use crate::sample_visitor::PathChecker;
+use crate::signature::Algorithm;
+
+const SIGNATURE_ALGORITHM_SETTING: &str = "signature_algorithm";
+
+// Comma-separated lists replacing default file type filters
+const INCLUDE_EXTENSIONS_SETTING: &str = "include_extensions";
+const EXCLUDE_DIRECTORIES_SETTING: &str = "exclude_directories";
 
 pub struct Project {
     pub dir: PathBuf,
     pub db_path: PathBuf,
-    pub path_checker: MediaPathChecker,
 }
 
 impl Project {
@@ -18,7 +26,6 @@ impl Project {
         Self {
             dir: dir.as_ref().to_owned(),
             db_path: db_path,
-            path_checker: MediaPathChecker::new(),
         }
     }
 
@@ -29,7 +36,75 @@ impl Project {
         Ok(conn)
     }
 
-    pub fn path_checker(&self) -> &impl PathChecker {
-        &self.path_checker
+    // Matches files considered by scan according to filters stored in project settings
+    pub fn path_checker(&self, conn: &Connection) -> Result<MediaPathChecker> {
+        let include_extensions = self.include_extensions(conn)?;
+        let exclude_directories = self.exclude_directories(conn)?;
+        MediaPathChecker::new(
+            &include_extensions
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>(),
+            &exclude_directories
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    // Algorithm used to compute signatures of new and changed files
+    pub fn signature_algorithm(&self, conn: &Connection) -> Result<Algorithm> {
+        match Setting::by_name(conn, SIGNATURE_ALGORITHM_SETTING)? {
+            Some(x) => Algorithm::try_from(x.value.as_str()),
+            None => Ok(Algorithm::default()),
+        }
+    }
+
+    pub fn set_signature_algorithm(&self, conn: &Connection, algorithm: Algorithm) -> Result<()> {
+        Setting::upsert(conn, SIGNATURE_ALGORITHM_SETTING, algorithm.as_str())
+    }
+
+    // Extensions of files tracked by scan
+    pub fn include_extensions(&self, conn: &Connection) -> Result<Vec<String>> {
+        get_list_setting(
+            conn,
+            INCLUDE_EXTENSIONS_SETTING,
+            &DEFAULT_MEDIA_FILE_EXTENSIONS,
+        )
+    }
+
+    // Empty list restores default
+    pub fn set_include_extensions(&self, conn: &Connection, extensions: &[String]) -> Result<()> {
+        set_list_setting(conn, INCLUDE_EXTENSIONS_SETTING, extensions)
+    }
+
+    // Names of directories whose contents are ignored by scan
+    pub fn exclude_directories(&self, conn: &Connection) -> Result<Vec<String>> {
+        get_list_setting(
+            conn,
+            EXCLUDE_DIRECTORIES_SETTING,
+            &DEFAULT_EXCLUDED_DIRECTORIES,
+        )
+    }
+
+    // Empty list restores default
+    pub fn set_exclude_directories(&self, conn: &Connection, directories: &[String]) -> Result<()> {
+        set_list_setting(conn, EXCLUDE_DIRECTORIES_SETTING, directories)
+    }
+}
+
+fn get_list_setting(conn: &Connection, name: &str, default: &[&str]) -> Result<Vec<String>> {
+    Ok(match Setting::by_name(conn, name)? {
+        Some(x) => x.value.split(',').map(String::from).collect(),
+        None => default.iter().map(|x| String::from(*x)).collect(),
+    })
+}
+
+fn set_list_setting(conn: &Connection, name: &str, values: &[String]) -> Result<()> {
+    if values.is_empty() {
+        Setting::delete_by_name(conn, name)?;
+        Ok(())
+    } else {
+        Setting::upsert(conn, name, &values.join(","))
     }
 }
@@ -0,0 +1,537 @@
+use rusqlite::types::Value;
+
+use crate::result::{user_error_result, Result};
+use crate::tag::{self, TagValue};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CompareOp {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CompareOp::Eq => "=",
+            CompareOp::Ne => "!=",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Expr {
+    Tag(String),
+    Compare(String, CompareOp, TagValue),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Op(CompareOp),
+    LeftParen,
+    RightParen,
+}
+
+impl Expr {
+    pub fn parse(s: &str) -> Result<Self> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            pos: 0,
+        };
+        let expr = parser.parse_or()?;
+        match parser.peek() {
+            Some(t) => user_error_result(format!(
+                "Unexpected {} in query \"{}\"",
+                describe_token(t),
+                s
+            )),
+            None => Ok(expr),
+        }
+    }
+
+    pub fn all_of(tags: &Vec<tag::Tag>) -> Option<Self> {
+        tags.iter()
+            .map(|x| match x.value() {
+                Some(v) => Self::Compare(String::from(x.as_str()), CompareOp::Eq, v.clone()),
+                None => Self::Tag(String::from(x.as_str())),
+            })
+            .fold(None, |acc, x| match acc {
+                Some(e) => Some(Self::And(Box::new(e), Box::new(x))),
+                None => Some(x),
+            })
+    }
+
+    // Compiles expression into a SQL expression over "files" with positional parameters
+    pub fn to_sql(&self) -> (String, Vec<Value>) {
+        let mut values = Vec::new();
+        let sql = self.to_sql_inner(&mut values);
+        (sql, values)
+    }
+
+    fn to_sql_inner(&self, values: &mut Vec<Value>) -> String {
+        match self {
+            Self::Tag(name) => {
+                values.push(Value::from(name.clone()));
+                // Tag matches files tagged with it or any of its descendants either directly
+                // or through their content
+                format!(
+                    "files.id IN (WITH RECURSIVE descendants(id) AS ({} UNION SELECT tags.id FROM tags INNER JOIN descendants ON tags.parent_id = descendants.id) SELECT file_tags.file_id FROM file_tags WHERE file_tags.tag_id IN (SELECT id FROM descendants) UNION SELECT instances.id FROM files AS instances INNER JOIN content_tags ON content_tags.content_id = instances.content_id WHERE content_tags.tag_id IN (SELECT id FROM descendants))",
+                    tag_ids_sql(values.len())
+                )
+            }
+            Self::Compare(name, op, value) => {
+                values.push(Value::from(name.clone()));
+                let name_index = values.len();
+                values.push(match value {
+                    TagValue::Integer(x) => Value::from(*x),
+                    TagValue::Decimal(x) => Value::from(*x),
+                    TagValue::Text(x) => Value::from(x.clone()),
+                });
+                let value_condition = |table: &str| {
+                    // Numeric values are only compared with numeric values and text with text
+                    let type_condition = if value.is_numeric() {
+                        format!("typeof({}.value) IN ('integer', 'real')", table)
+                    } else {
+                        format!("typeof({}.value) = 'text'", table)
+                    };
+                    format!(
+                        "{0}.tag_id IN ({1}) AND {2} AND {0}.value {3} ?{4}",
+                        table,
+                        tag_ids_sql(name_index),
+                        type_condition,
+                        op.as_str(),
+                        values.len()
+                    )
+                };
+                format!(
+                    "files.id IN (SELECT file_tags.file_id FROM file_tags WHERE {} UNION SELECT instances.id FROM files AS instances INNER JOIN content_tags ON content_tags.content_id = instances.content_id WHERE {})",
+                    value_condition("file_tags"),
+                    value_condition("content_tags")
+                )
+            }
+            Self::Not(e) => format!("NOT ({})", e.to_sql_inner(values)),
+            Self::And(lhs, rhs) => {
+                let lhs_sql = lhs.to_sql_inner(values);
+                let rhs_sql = rhs.to_sql_inner(values);
+                format!("({}) AND ({})", lhs_sql, rhs_sql)
+            }
+            Self::Or(lhs, rhs) => {
+                let lhs_sql = lhs.to_sql_inner(values);
+                let rhs_sql = rhs.to_sql_inner(values);
+                format!("({}) OR ({})", lhs_sql, rhs_sql)
+            }
+        }
+    }
+}
+
+// Selects ID of tag with given name or of tag that has given name as an alias
+fn tag_ids_sql(name_index: usize) -> String {
+    format!(
+        "SELECT id FROM tags WHERE name = ?{0} UNION SELECT tag_id FROM tag_aliases WHERE name = ?{0}",
+        name_index
+    )
+}
+
+struct Parser<'a> {
+    tokens: &'a Vec<Token>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.get(self.pos);
+        if token.is_some() {
+            self.pos += 1
+        }
+        token
+    }
+
+    fn next_is_keyword(&self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(w)) => w.eq_ignore_ascii_case(keyword),
+            _ => false,
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_and()?;
+        while self.next_is_keyword("OR") {
+            self.next();
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_unary()?;
+        while self.next_is_keyword("AND") {
+            self.next();
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        if self.next_is_keyword("NOT") {
+            self.next();
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr> {
+        match self.next() {
+            Some(Token::LeftParen) => {
+                let expr = self.parse_or()?;
+                match self.next() {
+                    Some(Token::RightParen) => Ok(expr),
+                    Some(t) => user_error_result(format!(
+                        "Expected \")\" but found {} in query",
+                        describe_token(t)
+                    )),
+                    None => user_error_result("Expected \")\" but reached end of query"),
+                }
+            }
+            Some(Token::Word(w)) if is_keyword(w) => user_error_result(format!(
+                "Expected tag but found keyword {} in query",
+                w.to_uppercase()
+            )),
+            Some(Token::Word(w)) => self.parse_comparison(w),
+            Some(Token::Quoted(s)) => self.parse_comparison(s),
+            Some(t) => user_error_result(format!(
+                "Expected tag but found {} in query",
+                describe_token(t)
+            )),
+            None => user_error_result("Expected tag but reached end of query"),
+        }
+    }
+
+    fn parse_comparison(&mut self, name: &str) -> Result<Expr> {
+        let op = match self.peek() {
+            Some(Token::Op(op)) => *op,
+            _ => return Ok(Expr::Tag(String::from(name))),
+        };
+        self.next();
+        match self.next() {
+            Some(Token::Word(w)) if !is_keyword(w) => {
+                Ok(Expr::Compare(String::from(name), op, TagValue::parse(w)))
+            }
+            Some(Token::Quoted(s)) => Ok(Expr::Compare(
+                String::from(name),
+                op,
+                TagValue::Text(s.clone()),
+            )),
+            Some(t) => user_error_result(format!(
+                "Expected value after {} but found {} in query",
+                op.as_str(),
+                describe_token(t)
+            )),
+            None => user_error_result(format!(
+                "Expected value after {} but reached end of query",
+                op.as_str()
+            )),
+        }
+    }
+}
+
+fn is_keyword(s: &str) -> bool {
+    ["AND", "OR", "NOT"]
+        .iter()
+        .any(|x| x.eq_ignore_ascii_case(s))
+}
+
+fn describe_token(token: &Token) -> String {
+    match token {
+        Token::Word(w) if is_keyword(w) => format!("keyword {}", w.to_uppercase()),
+        Token::Word(w) => format!("tag \"{}\"", w),
+        Token::Quoted(s) => format!("tag \"{}\"", s),
+        Token::Op(op) => format!("operator {}", op.as_str()),
+        Token::LeftParen => String::from("\"(\""),
+        Token::RightParen => String::from("\")\""),
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            _ if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::LeftParen)
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::RightParen)
+            }
+            '"' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => value.push(c),
+                        None => {
+                            return user_error_result(format!(
+                                "Unterminated quoted tag in query \"{}\"",
+                                s
+                            ))
+                        }
+                    }
+                }
+                tokens.push(Token::Quoted(value))
+            }
+            '=' | '!' | '<' | '>' => {
+                chars.next();
+                let followed_by_eq = chars.peek() == Some(&'=');
+                let op = match (c, followed_by_eq) {
+                    ('=', _) => CompareOp::Eq,
+                    ('!', true) => CompareOp::Ne,
+                    ('<', true) => CompareOp::Le,
+                    ('<', false) => CompareOp::Lt,
+                    ('>', true) => CompareOp::Ge,
+                    ('>', false) => CompareOp::Gt,
+                    _ => return user_error_result(format!("Expected \"!=\" in query \"{}\"", s)),
+                };
+                if followed_by_eq && c != '=' {
+                    chars.next();
+                }
+                tokens.push(Token::Op(op))
+            }
+            _ => {
+                let mut value = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "()\"=!<>".contains(c) {
+                        break;
+                    }
+                    value.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(value))
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::result::Error;
+
+    fn tag(s: &str) -> Box<Expr> {
+        Box::new(Expr::Tag(String::from(s)))
+    }
+
+    #[test]
+    fn test_parse_precedence() -> Result<()> {
+        assert_eq!(
+            Expr::Or(
+                tag("a"),
+                Box::new(Expr::And(tag("b"), Box::new(Expr::Not(tag("c")))))
+            ),
+            Expr::parse("a OR b AND NOT c")?
+        );
+        assert_eq!(
+            Expr::And(Box::new(Expr::Or(tag("a"), tag("b"))), tag("c")),
+            Expr::parse("(a or b) and c")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_quoted() -> Result<()> {
+        assert_eq!(
+            Expr::And(tag("and"), tag("hi hat")),
+            Expr::parse("\"and\" AND \"hi hat\"")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_comparison() -> Result<()> {
+        assert_eq!(
+            Expr::And(
+                Box::new(Expr::Compare(
+                    String::from("bpm"),
+                    CompareOp::Ge,
+                    TagValue::Integer(118)
+                )),
+                Box::new(Expr::Compare(
+                    String::from("bpm"),
+                    CompareOp::Lt,
+                    TagValue::Decimal(124.5)
+                ))
+            ),
+            Expr::parse("bpm>=118 AND bpm < 124.5")?
+        );
+        assert_eq!(
+            Expr::Compare(
+                String::from("key"),
+                CompareOp::Ne,
+                TagValue::Text(String::from("C min"))
+            ),
+            Expr::parse("key != \"C min\"")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for s in &[
+            "",
+            "a AND",
+            "(a OR b",
+            "a b",
+            "NOT",
+            "a )",
+            "\"a",
+            "bpm>=",
+            "bpm ! 1",
+            "bpm = AND",
+        ] {
+            match Expr::parse(s) {
+                Err(Error::User(_)) => {}
+                result => panic!("Expected user error for {:?}, got {:?}", s, result),
+            }
+        }
+    }
+
+    #[test]
+    fn test_to_sql() -> Result<()> {
+        let (sql, values) = Expr::parse("kick AND NOT acoustic")?.to_sql();
+        assert_eq!(2, values.len());
+        assert!(sql.contains("name = ?1"));
+        assert!(sql.contains("NOT (files.id IN"));
+        assert!(sql.contains("name = ?2"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_db() -> Result<()> {
+        use rusqlite::Connection;
+        use std::convert::TryFrom;
+
+        use crate::db::{run_migrations, ContentTag, File, FileTag, Tag, TagAlias};
+        use crate::file_info::FileInfo;
+        use crate::location::Location;
+        use crate::tag;
+        use crate::test_util::make_signature;
+
+        let conn = Connection::open_in_memory()?;
+        rusqlite::vtab::array::load_module(&conn)?;
+        run_migrations(&conn)?;
+
+        for (location, signature, tags) in &[
+            ("LOCATION0", 0, vec!["kick", "808"]),
+            ("LOCATION1", 1, vec!["kick", "acoustic"]),
+            ("LOCATION2", 2, vec!["808"]),
+            (
+                "LOCATION3",
+                3,
+                vec!["drums/kick/acoustic", "bpm=120", "key=Cmin"],
+            ),
+            ("LOCATION4", 4, vec!["bpm=90.5", "key=7", "hihat"]),
+        ] {
+            let file_id = File::insert(
+                &conn,
+                &FileInfo::new(Location::try_from(*location)?, make_signature(*signature)),
+            )?;
+            for t in tags {
+                let t = tag::Tag::from(t);
+                let tag_id = Tag::upsert(&conn, &t)?;
+                FileTag::upsert(&conn, file_id, tag_id, t.value())?;
+            }
+        }
+
+        let search = |s: &str| -> Result<Vec<String>> {
+            let (sql, values) = Expr::parse(s)?.to_sql();
+            let mut stmt = conn.prepare(&format!(
+                "SELECT location FROM files WHERE {} ORDER BY location",
+                sql
+            ))?;
+            let locations = stmt
+                .query_map(&values, |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(locations)
+        };
+
+        assert_eq!(vec!["LOCATION0"], search("kick AND 808")?);
+        assert_eq!(vec!["LOCATION0"], search("kick AND NOT acoustic")?);
+        assert_eq!(
+            vec!["LOCATION0", "LOCATION1", "LOCATION2"],
+            search("kick OR 808")?
+        );
+        assert_eq!(
+            vec!["LOCATION2", "LOCATION3", "LOCATION4"],
+            search("NOT kick")?
+        );
+        assert_eq!(vec!["LOCATION3"], search("bpm>=118 AND bpm<=124")?);
+        assert_eq!(vec!["LOCATION4"], search("bpm<100")?);
+        assert_eq!(vec!["LOCATION3"], search("key=Cmin")?);
+        assert_eq!(vec!["LOCATION4"], search("key>5")?);
+
+        TagAlias::insert(&conn, "hh", Tag::by_name(&conn, "hihat")?.unwrap().id)?;
+        TagAlias::insert(&conn, "tempo", Tag::by_name(&conn, "bpm")?.unwrap().id)?;
+        TagAlias::insert(&conn, "bd", Tag::by_name(&conn, "drums/kick")?.unwrap().id)?;
+        assert_eq!(vec!["LOCATION4"], search("hh")?);
+        assert_eq!(vec!["LOCATION4"], search("tempo<100")?);
+        assert_eq!(vec!["LOCATION3"], search("bd")?);
+        assert_eq!(vec!["LOCATION3"], search("drums")?);
+        assert_eq!(vec!["LOCATION3"], search("drums/kick")?);
+        assert!(search("drums/snare")?.is_empty());
+        assert!(search("unknown")?.is_empty());
+
+        // Content tags apply to every copy of content
+        let copy_id = File::insert(
+            &conn,
+            &FileInfo::new(Location::try_from("LOCATION5")?, make_signature(4)),
+        )?;
+        let content_id = File::by_location(&conn, &Location::try_from("LOCATION5")?)?
+            .unwrap()
+            .content_id;
+        let snare = tag::Tag::from("drums/snare");
+        ContentTag::upsert(&conn, content_id, Tag::upsert(&conn, &snare)?, None)?;
+        let rating = tag::Tag::from("rating=5");
+        ContentTag::upsert(
+            &conn,
+            content_id,
+            Tag::upsert(&conn, &rating)?,
+            rating.value(),
+        )?;
+        FileTag::upsert(
+            &conn,
+            copy_id,
+            Tag::by_name(&conn, "kick")?.unwrap().id,
+            None,
+        )?;
+        assert_eq!(vec!["LOCATION4", "LOCATION5"], search("drums/snare")?);
+        assert_eq!(
+            vec!["LOCATION3", "LOCATION4", "LOCATION5"],
+            search("drums")?
+        );
+        assert_eq!(vec!["LOCATION4", "LOCATION5"], search("rating>=5")?);
+        assert_eq!(vec!["LOCATION5"], search("drums/snare AND kick")?);
+        Ok(())
+    }
+}
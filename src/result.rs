@@ -72,6 +72,12 @@ impl std::convert::From<std::path::StripPrefixError> for Error {
     }
 }
 
+impl std::convert::From<std::time::SystemTimeError> for Error {
+    fn from(error: std::time::SystemTimeError) -> Self {
+        internal_error("SystemTime", error.to_string())
+    }
+}
+
 /*
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
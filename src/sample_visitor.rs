@@ -1,26 +1,50 @@
 use std::fs::{self, DirEntry};
 use std::path::Path;
 
-use crate::result::Result;
+use crate::result::{Error, Result};
 
 pub trait PathChecker {
     fn matches(&self, path: &impl AsRef<Path>) -> Result<bool>;
 }
 
+// Error handler that stops visit at first error
+pub fn fail_fast(_path: &Path, error: Error) -> Result<()> {
+    Err(error)
+}
+
+// Visits matching files passing errors for individual files and directories to
+// error handler which decides whether to continue or not
 pub fn visit(
     dir: &Path,
     path_checker: &impl PathChecker,
     cb: &dyn Fn(&DirEntry) -> Result<()>,
+    on_error: &dyn Fn(&Path, Error) -> Result<()>,
 ) -> Result<()> {
     if dir.is_dir() {
-        for entry in fs::read_dir(dir)? {
-            let entry = entry?;
+        let entries = match fs::read_dir(dir) {
+            Ok(x) => x,
+            Err(e) => return on_error(dir, e.into()),
+        };
+        for entry in entries {
+            let entry = match entry {
+                Ok(x) => x,
+                Err(e) => {
+                    on_error(dir, e.into())?;
+                    continue;
+                }
+            };
             let path = entry.path();
             if path.is_dir() {
-                visit(&path, path_checker, cb)?;
+                visit(&path, path_checker, cb, on_error)?;
             } else {
-                if path_checker.matches(&path)? {
-                    cb(&entry)?;
+                match path_checker.matches(&path) {
+                    Ok(true) => {
+                        if let Err(e) = cb(&entry) {
+                            on_error(&path, e)?
+                        }
+                    }
+                    Ok(false) => {}
+                    Err(e) => on_error(&path, e)?,
                 }
             }
         }
@@ -0,0 +1,379 @@
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, sync_channel, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::file_info::FileStat;
+use crate::location::Location;
+use crate::result::{internal_error, Error, Result};
+use crate::sample_visitor::{self, PathChecker};
+use crate::signature::{Algorithm, QuickSignature, Signature};
+
+pub struct ScanItem {
+    pub path: PathBuf,
+    pub location: Location,
+    pub stat: FileStat,
+    // None if file is unchanged since it was last hashed
+    pub signature: Option<Signature>,
+    // None if file is unchanged and its quick signature is already known
+    pub quick_signature: Option<QuickSignature>,
+}
+
+// State of file recorded by previous scan
+pub struct KnownFile {
+    pub stat: FileStat,
+    pub has_quick_signature: bool,
+}
+
+pub struct ScanOptions {
+    pub jobs: usize,
+    pub full: bool,
+}
+
+struct Job {
+    seq: u64,
+    path: PathBuf,
+    // Set if walker failed to read path so that failure is reported in sequence
+    error: Option<Error>,
+}
+
+// Walks given paths underneath base directory on one thread and hashes files on a pool
+// of worker threads while the calling thread receives the results in the same order as
+// a sequential walk along with any error for files that could not be read
+pub fn scan(
+    dir: &Path,
+    paths: &Vec<PathBuf>,
+    path_checker: impl PathChecker + Send + 'static,
+    known: HashMap<Location, KnownFile>,
+    algorithm: Algorithm,
+    options: &ScanOptions,
+    cb: &mut dyn FnMut(&Path, Result<ScanItem>) -> Result<()>,
+) -> Result<()> {
+    let jobs = options.jobs.max(1);
+    let (job_tx, job_rx) = sync_channel::<Job>(jobs * 4);
+    let (result_tx, result_rx) = channel::<(u64, PathBuf, Result<ScanItem>)>();
+
+    let walker = {
+        let paths = paths.clone();
+        thread::spawn(move || -> Result<()> {
+            let seq = Cell::new(0);
+            let send = |path: PathBuf, error: Option<Error>| {
+                job_tx
+                    .send(Job {
+                        seq: seq.get(),
+                        path: path,
+                        error: error,
+                    })
+                    .map_err(|_| internal_error("Scanner", "Worker threads stopped"))?;
+                seq.set(seq.get() + 1);
+                Ok(())
+            };
+            visit_paths(
+                &paths,
+                &path_checker,
+                &|path| send(path, None),
+                &|path, error| send(path.to_path_buf(), Some(error)),
+            )
+        })
+    };
+
+    let job_rx = Arc::new(Mutex::new(job_rx));
+    let known = Arc::new(known);
+    let workers = (0..jobs)
+        .map(|_| {
+            let dir = dir.to_path_buf();
+            let job_rx = Arc::clone(&job_rx);
+            let result_tx = result_tx.clone();
+            let known = Arc::clone(&known);
+            let full = options.full;
+            thread::spawn(move || {
+                while let Some(job) = next_job(&job_rx) {
+                    let result = match job.error {
+                        Some(error) => Err(error),
+                        None => hash_file(&dir, job.path.clone(), &known, algorithm, full),
+                    };
+                    if result_tx.send((job.seq, job.path, result)).is_err() {
+                        break;
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    drop(job_rx);
+    drop(result_tx);
+
+    // Receiver is dropped before joining so that workers stop at their next result and
+    // walker stops once workers no longer accept jobs
+    let received = receive_in_order(result_rx, cb);
+
+    for worker in workers {
+        if worker.join().is_err() {
+            return Err(internal_error("Scanner", "Worker thread panicked"));
+        }
+    }
+    let walked = match walker.join() {
+        Ok(result) => result,
+        Err(_) => Err(internal_error("Scanner", "Directory walker panicked")),
+    };
+    received?;
+    walked
+}
+
+// Reorders results so that they are processed exactly as a sequential scan would
+fn receive_in_order(
+    result_rx: Receiver<(u64, PathBuf, Result<ScanItem>)>,
+    cb: &mut dyn FnMut(&Path, Result<ScanItem>) -> Result<()>,
+) -> Result<()> {
+    let mut pending = BTreeMap::new();
+    let mut next_seq = 0;
+    for (seq, path, result) in result_rx {
+        pending.insert(seq, (path, result));
+        while let Some((path, result)) = pending.remove(&next_seq) {
+            cb(&path, result)?;
+            next_seq += 1;
+        }
+    }
+    Ok(())
+}
+
+// Counts files and their total size so that scan progress can be estimated
+pub fn count_files(paths: &Vec<PathBuf>, path_checker: &impl PathChecker) -> Result<(u64, u64)> {
+    let files = Cell::new(0);
+    let bytes = Cell::new(0);
+    visit_paths(
+        paths,
+        path_checker,
+        &|path| {
+            files.set(files.get() + 1);
+            bytes.set(bytes.get() + path.metadata().map(|x| x.len()).unwrap_or(0));
+            Ok(())
+        },
+        // Failures are reported by scan itself
+        &|_path, _error| Ok(()),
+    )?;
+    Ok((files.get(), bytes.get()))
+}
+
+// Visits files underneath each directory and any individual files in given paths
+fn visit_paths(
+    paths: &Vec<PathBuf>,
+    path_checker: &impl PathChecker,
+    cb: &dyn Fn(PathBuf) -> Result<()>,
+    on_error: &dyn Fn(&Path, Error) -> Result<()>,
+) -> Result<()> {
+    for path in paths {
+        if path.is_dir() {
+            sample_visitor::visit(path, path_checker, &|entry| cb(entry.path()), on_error)?;
+        } else {
+            match path_checker.matches(path) {
+                Ok(true) => cb(path.clone())?,
+                Ok(false) => {}
+                Err(error) => on_error(path, error)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+fn next_job(job_rx: &Mutex<Receiver<Job>>) -> Option<Job> {
+    match job_rx.lock() {
+        Ok(rx) => rx.recv().ok(),
+        Err(_) => None,
+    }
+}
+
+fn hash_file(
+    dir: &Path,
+    path: PathBuf,
+    known: &HashMap<Location, KnownFile>,
+    algorithm: Algorithm,
+    full: bool,
+) -> Result<ScanItem> {
+    let location = Location::from_path(dir, &path)?;
+    let stat = FileStat::from_path(&path)?;
+
+    // Only rehash files whose size or modification time has changed
+    let (signature, quick_signature) = match known.get(&location) {
+        Some(x) if !full && x.stat == stat => {
+            // Files scanned before quick signatures were recorded are only partially read
+            let quick_signature = if x.has_quick_signature {
+                None
+            } else {
+                Some(QuickSignature::from_file(&path)?)
+            };
+            (None, quick_signature)
+        }
+        // Quick signature cannot save hashing here: changed or new file needs full
+        // signature to be recorded as content whether or not its quick signature
+        // matches any known file, and a match must be confirmed by full signature anyway
+        _ => (
+            Some(Signature::from_file(&path, algorithm)?),
+            Some(QuickSignature::from_file(&path)?),
+        ),
+    };
+
+    Ok(ScanItem {
+        path: path,
+        location: location,
+        stat: stat,
+        signature: signature,
+        quick_signature: quick_signature,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::fs;
+
+    use super::*;
+    use crate::test_util::TempPath;
+
+    #[derive(Clone)]
+    struct AllPathChecker;
+
+    impl PathChecker for AllPathChecker {
+        fn matches(&self, _path: &impl AsRef<Path>) -> Result<bool> {
+            Ok(true)
+        }
+    }
+
+    fn scan_locations(dir: &Path, jobs: usize) -> Result<Vec<(String, String)>> {
+        let mut items = Vec::new();
+        scan(
+            dir,
+            &vec![dir.to_path_buf()],
+            AllPathChecker,
+            HashMap::new(),
+            Algorithm::default(),
+            &ScanOptions {
+                jobs: jobs,
+                full: false,
+            },
+            &mut |_path, result| {
+                let item = result?;
+                items.push((
+                    String::from(item.location.as_str()),
+                    item.signature?.to_string(),
+                ));
+                Ok(())
+            },
+        )?;
+        Ok(items)
+    }
+
+    #[test]
+    fn test_scan_matches_sequential_order() -> Result<()> {
+        let temp = TempPath::new("scanner");
+        let dir = temp.path().to_path_buf();
+        for i in 0..5 {
+            let sub_dir = dir.join(format!("dir{}", i));
+            fs::create_dir_all(&sub_dir)?;
+            for j in 0..10 {
+                fs::write(sub_dir.join(format!("file{}", j)), vec![i as u8; j * 100])?;
+            }
+        }
+
+        let expected = RefCell::new(Vec::new());
+        sample_visitor::visit(
+            &dir,
+            &AllPathChecker,
+            &|entry| {
+                let location = Location::from_path(&dir, entry.path())?;
+                expected.borrow_mut().push(String::from(location.as_str()));
+                Ok(())
+            },
+            &sample_visitor::fail_fast,
+        )?;
+
+        let sequential = scan_locations(&dir, 1)?;
+        let parallel = scan_locations(&dir, 8)?;
+
+        let (file_count, byte_count) = count_files(&vec![dir.clone()], &AllPathChecker)?;
+        let (partial_count, _) = count_files(
+            &vec![dir.join("dir1"), dir.join("dir3").join("file5")],
+            &AllPathChecker,
+        )?;
+
+        assert_eq!(50, file_count);
+        assert_eq!(11, partial_count);
+        assert_eq!(5 * 4500, byte_count);
+        assert_eq!(50, parallel.len());
+        assert_eq!(sequential, parallel);
+        assert_eq!(
+            expected.into_inner(),
+            parallel.into_iter().map(|x| x.0).collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_scan_reports_failures() -> Result<()> {
+        let temp = TempPath::new("scanner-failures");
+        let dir = temp.path().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("file0"), vec![0u8; 100])?;
+        fs::write(dir.join("file1"), vec![1u8; 100])?;
+        std::os::unix::fs::symlink(dir.join("missing"), dir.join("broken"))?;
+
+        let mut items = Vec::new();
+        let mut failures = Vec::new();
+        let result = scan(
+            &dir,
+            &vec![dir.clone()],
+            AllPathChecker,
+            HashMap::new(),
+            Algorithm::default(),
+            &ScanOptions {
+                jobs: 2,
+                full: false,
+            },
+            &mut |path, result| {
+                match result {
+                    Ok(item) => items.push(item.location),
+                    Err(_) => failures.push(path.to_path_buf()),
+                }
+                Ok(())
+            },
+        );
+        result?;
+
+        assert_eq!(2, items.len());
+        assert_eq!(vec![dir.join("broken")], failures);
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_stops_on_callback_error() -> Result<()> {
+        let temp = TempPath::new("scanner-callback-error");
+        let dir = temp.path().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        for i in 0..100 {
+            fs::write(dir.join(format!("file{}", i)), vec![i as u8; 100])?;
+        }
+
+        let mut count = 0;
+        let result = scan(
+            &dir,
+            &vec![dir.clone()],
+            AllPathChecker,
+            HashMap::new(),
+            Algorithm::default(),
+            &ScanOptions {
+                jobs: 4,
+                full: false,
+            },
+            &mut |_path, _result| {
+                count += 1;
+                Err(internal_error("Test", "Callback failed"))
+            },
+        );
+
+        assert_eq!(1, count);
+        assert!(matches!(result, Err(Error::Internal("Test", _))));
+        Ok(())
+    }
+}
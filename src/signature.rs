@@ -1,55 +1,281 @@
-use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
 use sha2::{Digest, Sha256};
 use std::convert::TryFrom;
 use std::fs::File;
-use std::io::copy;
+use std::io::{copy, Read, Seek, SeekFrom};
+use std::ops::Range;
 use std::path::Path;
 
-use crate::result::{Error, Result};
+use crate::audio;
+use crate::result::{user_error_result, Error, Result};
 
+// Size of each block hashed by quick signature
+const QUICK_BLOCK_SIZE: u64 = 64 * 1024;
+
+// Hash algorithm used to compute signature and recorded as its prefix
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
+pub enum Algorithm {
+    Sha256,
+    Blake3,
+}
+
+impl Algorithm {
+    pub const NAMES: [&'static str; 2] = ["sha256", "blake3"];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha256",
+            Self::Blake3 => "blake3",
+        }
+    }
+
+    // Number of bytes in digest
+    pub fn digest_size(&self) -> usize {
+        match self {
+            Self::Sha256 => 32,
+            Self::Blake3 => 32,
+        }
+    }
+}
+
+impl Default for Algorithm {
+    fn default() -> Self {
+        Self::Sha256
+    }
+}
+
+impl TryFrom<&str> for Algorithm {
+    type Error = Error;
+
+    fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+        match value {
+            "sha256" => Ok(Self::Sha256),
+            "blake3" => Ok(Self::Blake3),
+            _ => user_error_result(format!("Unsupported signature algorithm \"{}\"", value)),
+        }
+    }
+}
+
+// Bytes of file covered by signature
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
+pub enum Scope {
+    File,
+    // Audio payload only so that editing metadata does not change signature
+    Audio,
+}
+
+impl Scope {
+    // Appended to algorithm prefix of signatures that only cover audio payload
+    const AUDIO_SUFFIX: &'static str = "-audio";
+
+    fn suffix(&self) -> &'static str {
+        match self {
+            Self::File => "",
+            Self::Audio => Self::AUDIO_SUFFIX,
+        }
+    }
+}
+
+// Hash of bytes covered by signature along with how and from how many bytes it was computed
 #[derive(Debug, Eq, Hash, PartialEq)]
-pub struct Signature(String);
+pub struct Signature {
+    algorithm: Algorithm,
+    scope: Scope,
+    digest: Vec<u8>,
+    size: u64,
+}
 
 impl Signature {
+    pub fn new(algorithm: Algorithm, scope: Scope, digest: Vec<u8>, size: u64) -> Self {
+        Self {
+            algorithm: algorithm,
+            scope: scope,
+            digest: digest,
+            size: size,
+        }
+    }
+
+    // Hashes audio payload of formats that support it and whole file otherwise
+    pub fn from_file(path: &Path, algorithm: Algorithm) -> Result<Self> {
+        let (f, scope, range) = open_payload(path)?;
+        Self::hash(f, algorithm, scope, range)
+    }
+
+    // Hashes same bytes as existing signature with given scope so that files signed
+    // before their format was signed by audio payload can still be compared: file without
+    // audio payload is hashed whole whatever the scope
+    pub fn from_file_with_scope(path: &Path, algorithm: Algorithm, scope: Scope) -> Result<Self> {
+        let (f, payload_scope, range) = open_payload(path)?;
+        if scope == Scope::File && payload_scope != Scope::File {
+            let len = f.metadata()?.len();
+            Self::hash(f, algorithm, Scope::File, 0..len)
+        } else {
+            Self::hash(f, algorithm, payload_scope, range)
+        }
+    }
+
+    fn hash(mut f: File, algorithm: Algorithm, scope: Scope, range: Range<u64>) -> Result<Self> {
+        let size = range.end - range.start;
+        f.seek(SeekFrom::Start(range.start))?;
+        let mut payload = f.take(size);
+        let digest = match algorithm {
+            Algorithm::Sha256 => {
+                let mut hasher = Sha256::new();
+                copy(&mut payload, &mut hasher)?;
+                hasher.finalize().to_vec()
+            }
+            Algorithm::Blake3 => {
+                let mut hasher = blake3::Hasher::new();
+                copy(&mut payload, &mut hasher)?;
+                hasher.finalize().to_vec()
+            }
+        };
+        Ok(Self::new(algorithm, scope, digest, size))
+    }
+
+    // Scope that signature of file would be computed with without hashing file
+    pub fn scope_of_file(path: &Path) -> Result<Scope> {
+        let (_, scope, _) = open_payload(path)?;
+        Ok(scope)
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub fn scope(&self) -> Scope {
+        self.scope
+    }
+
+    pub fn digest(&self) -> &[u8] {
+        &self.digest
+    }
+
+    // Number of bytes covered by signature: size of audio payload rather than of whole
+    // file for audio signatures
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl std::fmt::Display for Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}{}:", self.algorithm.as_str(), self.scope.suffix())?;
+        for x in &self.digest {
+            write!(f, "{:02x}", x)?;
+        }
+        write!(f, ":{}", self.size)
+    }
+}
+
+// Opens file and determines range of bytes covered by its signature
+fn open_payload(path: &Path) -> Result<(File, Scope, Range<u64>)> {
+    let mut f = File::open(&path)?;
+    Ok(match audio::payload_range(&mut f, audio::is_mp3(path))? {
+        Some(x) => (f, Scope::Audio, x),
+        None => {
+            let len = f.metadata()?.len();
+            (f, Scope::File, 0..len)
+        }
+    })
+}
+
+// Cheap signature computed from size and first, middle and last blocks of bytes covered
+// by full signature: different quick signatures mean different content while equal quick
+// signatures must be confirmed by comparing full signatures
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct QuickSignature(String);
+
+impl QuickSignature {
     pub fn from_file(path: &Path) -> Result<Self> {
-        let mut f = File::open(&path)?;
-        let size = f.metadata()?.len();
+        let (mut f, _, range) = open_payload(path)?;
+        let size = range.end - range.start;
+        // Small files are hashed in their entirety
+        let blocks = if size <= 3 * QUICK_BLOCK_SIZE {
+            vec![(range.start, size)]
+        } else {
+            vec![
+                (range.start, QUICK_BLOCK_SIZE),
+                (
+                    range.start + size / 2 - QUICK_BLOCK_SIZE / 2,
+                    QUICK_BLOCK_SIZE,
+                ),
+                (range.end - QUICK_BLOCK_SIZE, QUICK_BLOCK_SIZE),
+            ]
+        };
         let mut hasher = Sha256::new();
-        copy(&mut f, &mut hasher)?;
-        let hash = hasher.finalize();
-        Ok(Self(format!("{:x}:{}", hash, size)))
+        for (offset, len) in blocks {
+            f.seek(SeekFrom::Start(offset))?;
+            copy(&mut f.by_ref().take(len), &mut hasher)?;
+        }
+        Ok(Self(format!("{:x}:{}", hasher.finalize(), size)))
     }
 
     pub fn as_str(&self) -> &str {
         &self.0
     }
+}
 
-    pub fn into_string(self) -> String {
-        self.0
+impl FromSql for QuickSignature {
+    fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+        value.as_str().and_then(|s| Ok(Self(String::from(s))))
     }
+}
 
-    fn new(value: &str) -> Self {
-        Self(String::from(value))
+impl ToSql for QuickSignature {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.0.as_str()))
     }
 }
 
 impl FromSql for Signature {
     fn column_result(value: ValueRef) -> FromSqlResult<Self> {
-        value.as_str().and_then(|s| Ok(Self::new(s)))
+        value.as_str().and_then(|s| {
+            Self::try_from(s)
+                .map_err(|_| FromSqlError::Other(format!("Invalid signature \"{}\"", s).into()))
+        })
     }
 }
 
 impl ToSql for Signature {
     fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
-        Ok(ToSqlOutput::from(self.0.as_str()))
+        Ok(ToSqlOutput::from(self.to_string()))
     }
 }
 
+// Parses signature of form ALGORITHM[-audio]:DIGEST:SIZE with lower-case hexadecimal
+// digest of length produced by algorithm
 impl TryFrom<&str> for Signature {
     type Error = Error;
 
     fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
-        Ok(Signature::new(&value))
+        let parts = value.split(':').collect::<Vec<_>>();
+        if parts.len() != 3 {
+            return user_error_result(format!("Invalid signature \"{}\"", value));
+        }
+
+        let (algorithm, scope) = match parts[0].strip_suffix(Scope::AUDIO_SUFFIX) {
+            Some(x) => (Algorithm::try_from(x)?, Scope::Audio),
+            None => (Algorithm::try_from(parts[0])?, Scope::File),
+        };
+
+        let hex = parts[1];
+        let digest = if hex.len() == 2 * algorithm.digest_size()
+            && hex.bytes().all(|x| matches!(x, b'0'..=b'9' | b'a'..=b'f'))
+        {
+            (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .ok()
+        } else {
+            None
+        };
+
+        match (digest, parts[2].parse::<u64>()) {
+            (Some(digest), Ok(size)) => Ok(Self::new(algorithm, scope, digest, size)),
+            _ => user_error_result(format!("Invalid signature \"{}\"", value)),
+        }
     }
 }
 
@@ -58,30 +284,194 @@ mod tests {
     use std::convert::TryInto;
 
     use super::*;
+    use crate::test_util::TempPath;
+
+    const SHA256_ABC: &str =
+        "sha256:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad:3";
+
+    const BLAKE3_ABC: &str =
+        "blake3:6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85:3";
 
     #[test]
     fn test_try_from() -> Result<()> {
-        let signature = Signature::try_from("SIGNATURE")?;
-        assert_eq!("SIGNATURE", signature.as_str());
+        let signature = Signature::try_from(SHA256_ABC)?;
+        assert_eq!(Algorithm::Sha256, signature.algorithm());
+        assert_eq!(Scope::File, signature.scope());
+        assert_eq!(32, signature.digest().len());
+        assert_eq!(0xba, signature.digest()[0]);
+        assert_eq!(3, signature.size());
+        assert_eq!(SHA256_ABC, signature.to_string());
         Ok(())
     }
 
+    #[test]
+    fn test_try_from_invalid() {
+        for value in &[
+            "SIGNATURE",
+            "",
+            "sha256:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            "md5:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad:3",
+            "sha256:ba7816bf:3",
+            "sha256:BA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD:3",
+            "sha256:zz7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad:3",
+            "sha256:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad:-3",
+            "sha256-video:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad:3",
+        ] {
+            assert!(Signature::try_from(*value).is_err(), "{}", value);
+        }
+    }
+
     #[test]
     fn test_try_into() -> Result<()> {
-        let signature: Signature = "SIGNATURE".try_into()?;
-        assert_eq!("SIGNATURE", signature.as_str());
+        let signature: Signature = BLAKE3_ABC.try_into()?;
+        assert_eq!(BLAKE3_ABC, signature.to_string());
         Ok(())
     }
 
     #[test]
     fn test_eq() -> Result<()> {
         assert_eq!(
-            Signature::try_from("SIGNATURE")?,
-            Signature::try_from("SIGNATURE")?
+            Signature::try_from(SHA256_ABC)?,
+            Signature::try_from(SHA256_ABC)?
+        );
+        assert!(Signature::try_from(SHA256_ABC)?.eq(&Signature::new(
+            Algorithm::Sha256,
+            Scope::File,
+            Signature::try_from(SHA256_ABC)?.digest().to_vec(),
+            3
+        )));
+        assert!(Signature::try_from(SHA256_ABC)? != Signature::try_from(BLAKE3_ABC)?);
+        assert!(
+            Signature::try_from(SHA256_ABC)?
+                != Signature::new(Algorithm::Sha256, Scope::File, vec![0; 32], 3)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_algorithm() -> Result<()> {
+        assert_eq!(Algorithm::Sha256, Algorithm::try_from("sha256")?);
+        assert_eq!(Algorithm::Blake3, Algorithm::try_from("blake3")?);
+        assert!(Algorithm::try_from("md5").is_err());
+        assert_eq!(
+            Algorithm::Blake3,
+            Signature::try_from(BLAKE3_ABC)?.algorithm()
         );
-        assert!(Signature::try_from("SIGNATURE")?.eq(&Signature::try_from("SIGNATURE")?));
-        assert!(!Signature::try_from("SIGNATURE0")?.eq(&Signature::try_from("SIGNATURE1")?));
-        assert!(Signature::try_from("SIGNATURE0")? != Signature::try_from("SIGNATURE1")?);
+        let signature =
+            Signature::try_from(SHA256_ABC.replace("sha256:", "sha256-audio:").as_str())?;
+        assert_eq!(Algorithm::Sha256, signature.algorithm());
+        assert_eq!(Scope::Audio, signature.scope());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sql() -> Result<()> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        let signature = conn.query_row(
+            "SELECT ?1",
+            rusqlite::params![Signature::try_from(SHA256_ABC)?],
+            |row| row.get::<_, Signature>(0),
+        )?;
+        assert_eq!(SHA256_ABC, signature.to_string());
+        let error = conn
+            .query_row("SELECT 'SIGNATURE'", rusqlite::NO_PARAMS, |row| {
+                row.get::<_, Signature>(0)
+            })
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Invalid signature \"SIGNATURE\""));
+        Ok(())
+    }
+
+    #[test]
+    fn test_quick_signature() -> Result<()> {
+        let temp = TempPath::new("quick");
+        let path = temp.path();
+        let mut bytes = vec![0u8; 4 * QUICK_BLOCK_SIZE as usize];
+        std::fs::write(&path, &bytes)?;
+        let before = QuickSignature::from_file(&path);
+
+        // Bytes between sampled blocks are not covered by quick signature
+        bytes[QUICK_BLOCK_SIZE as usize + 1] = 1;
+        std::fs::write(&path, &bytes)?;
+        let unsampled = QuickSignature::from_file(&path);
+        let last = bytes.len() - 1;
+        bytes[last] = 1;
+        std::fs::write(&path, &bytes)?;
+        let sampled = QuickSignature::from_file(&path);
+
+        let before = before?;
+        assert!(before.as_str().ends_with(":262144"));
+        assert_eq!(before, unsampled?);
+        assert_ne!(before, sampled?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_file_audio() -> Result<()> {
+        let wav = |list: &[u8]| {
+            let mut bytes = b"RIFF\0\0\0\0WAVE".to_vec();
+            bytes.extend(b"LIST");
+            bytes.extend(&(list.len() as u32).to_le_bytes());
+            bytes.extend(list);
+            bytes.extend(b"data\x03\0\0\0abc\0");
+            bytes
+        };
+        let temp = TempPath::new("signature-audio.wav");
+        let path = temp.path();
+        std::fs::write(&path, wav(b"INFO"))?;
+        let before = Signature::from_file(&path, Algorithm::Sha256);
+        std::fs::write(&path, wav(b"INFOISFT"))?;
+        let after = Signature::from_file(&path, Algorithm::Sha256);
+        let scope = Signature::scope_of_file(&path);
+        assert_eq!(
+            "sha256-audio:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad:3",
+            before?.to_string()
+        );
+        assert_eq!(
+            "sha256-audio:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad:3",
+            after?.to_string()
+        );
+        assert_eq!(Scope::Audio, scope?);
+
+        let whole = Signature::from_file_with_scope(&path, Algorithm::Sha256, Scope::File)?;
+        assert_eq!(Scope::File, whole.scope());
+        assert_eq!(
+            Sha256::digest(&wav(b"INFOISFT")).to_vec(),
+            whole.digest().to_vec()
+        );
+        assert_eq!(
+            Signature::from_file(&path, Algorithm::Sha256)?,
+            Signature::from_file_with_scope(&path, Algorithm::Sha256, Scope::Audio)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_file_sync_like_prefix() -> Result<()> {
+        let bytes = [0xff, 0xfb, 0x90, 0x64, 0, 0, 0, 0, 0, 0, 0, 0];
+        let temp = TempPath::new("signature-sync.bin");
+        let path = temp.path();
+        std::fs::write(&path, &bytes)?;
+        assert_eq!(Scope::File, Signature::scope_of_file(&path)?);
+
+        let temp = TempPath::new("signature-sync.mp3");
+        let path = temp.path();
+        std::fs::write(&path, &bytes)?;
+        assert_eq!(Scope::Audio, Signature::scope_of_file(&path)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_file() -> Result<()> {
+        let temp = TempPath::new("signature");
+        let path = temp.path();
+        std::fs::write(&path, b"abc")?;
+        let sha256 = Signature::from_file(&path, Algorithm::Sha256);
+        let blake3 = Signature::from_file(&path, Algorithm::Blake3);
+        assert_eq!(SHA256_ABC, sha256?.to_string());
+        assert_eq!(BLAKE3_ABC, blake3?.to_string());
         Ok(())
     }
 }
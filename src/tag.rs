@@ -1,12 +1,171 @@
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TagValue {
+    Integer(i64),
+    Decimal(f64),
+    Text(String),
+}
+
+impl TagValue {
+    // Infers type of value from its syntax: quoted values are always text
+    pub fn parse(s: &str) -> Self {
+        if s.len() >= 2 && s.starts_with('"') && s.ends_with('"') {
+            return TagValue::Text(String::from(&s[1..s.len() - 1]));
+        }
+        if let Ok(x) = s.parse::<i64>() {
+            return TagValue::Integer(x);
+        }
+        if s.chars().any(|c| c.is_ascii_digit()) {
+            if let Ok(x) = s.parse::<f64>() {
+                if x.is_finite() {
+                    return TagValue::Decimal(x);
+                }
+            }
+        }
+        TagValue::Text(String::from(s))
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            TagValue::Integer(_) => "integer",
+            TagValue::Decimal(_) => "decimal",
+            TagValue::Text(_) => "text",
+        }
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        match self {
+            TagValue::Integer(_) | TagValue::Decimal(_) => true,
+            TagValue::Text(_) => false,
+        }
+    }
+}
+
+impl std::fmt::Display for TagValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TagValue::Integer(x) => write!(f, "{}", x),
+            TagValue::Decimal(x) => write!(f, "{:?}", x),
+            TagValue::Text(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+impl FromSql for TagValue {
+    fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+        match value {
+            ValueRef::Integer(x) => Ok(TagValue::Integer(x)),
+            ValueRef::Real(x) => Ok(TagValue::Decimal(x)),
+            ValueRef::Text(_) => value.as_str().map(|s| TagValue::Text(String::from(s))),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+impl ToSql for TagValue {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(match self {
+            TagValue::Integer(x) => ToSqlOutput::from(*x),
+            TagValue::Decimal(x) => ToSqlOutput::from(*x),
+            TagValue::Text(x) => ToSqlOutput::from(x.as_str()),
+        })
+    }
+}
+
 #[derive(Debug)]
-pub struct Tag<'a>(&'a str);
+pub struct Tag<'a> {
+    name: &'a str,
+    value: Option<TagValue>,
+}
 
 impl<'a> Tag<'a> {
+    pub const SEPARATOR: char = '/';
+    pub const VALUE_SEPARATOR: char = '=';
+
+    // Parses "name" or "name=value"
     pub fn from(s: &'a str) -> Self {
-        Tag(s)
+        match s.find(Self::VALUE_SEPARATOR) {
+            Some(i) => Self::new(&s[..i], Some(TagValue::parse(&s[i + 1..]))),
+            None => Self::new(s, None),
+        }
+    }
+
+    pub fn new(name: &'a str, value: Option<TagValue>) -> Self {
+        Tag {
+            name: name.trim_matches(Self::SEPARATOR),
+            value: value,
+        }
     }
 
     pub fn as_str(&self) -> &str {
-        self.0
+        self.name
+    }
+
+    pub fn value(&self) -> Option<&TagValue> {
+        self.value.as_ref()
+    }
+
+    pub fn parent(&self) -> Option<Tag<'a>> {
+        self.name
+            .rfind(Self::SEPARATOR)
+            .map(|i| Self::new(&self.name[..i], None))
+    }
+
+    pub fn leaf_name(&self) -> &str {
+        match self.name.rfind(Self::SEPARATOR) {
+            Some(i) => &self.name[i + 1..],
+            None => self.name,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from() {
+        assert_eq!("drums/kick", Tag::from("/drums/kick/").as_str());
+    }
+
+    #[test]
+    fn test_from_value() {
+        let tag = Tag::from("bpm=120");
+        assert_eq!("bpm", tag.as_str());
+        assert_eq!(Some(&TagValue::Integer(120)), tag.value());
+        assert_eq!(
+            Some(&TagValue::Text(String::from("Cmin"))),
+            Tag::from("key=Cmin").value()
+        );
+        assert_eq!(None, Tag::from("kick").value());
+    }
+
+    #[test]
+    fn test_parse_value() {
+        assert_eq!(TagValue::Integer(-4), TagValue::parse("-4"));
+        assert_eq!(TagValue::Decimal(1.5), TagValue::parse("1.5"));
+        assert_eq!(
+            TagValue::Text(String::from("120")),
+            TagValue::parse("\"120\"")
+        );
+        assert_eq!(TagValue::Text(String::from("inf")), TagValue::parse("inf"));
+        assert_eq!(TagValue::Text(String::from("")), TagValue::parse(""));
+    }
+
+    #[test]
+    fn test_parent() {
+        let tag = Tag::from("drums/kick/acoustic");
+        let parent = tag.parent().unwrap();
+        assert_eq!("drums/kick", parent.as_str());
+        let grandparent = parent.parent().unwrap();
+        assert_eq!("drums", grandparent.as_str());
+        assert!(grandparent.parent().is_none());
+    }
+
+    #[test]
+    fn test_leaf_name() {
+        assert_eq!("acoustic", Tag::from("drums/kick/acoustic").leaf_name());
+        assert_eq!("drums", Tag::from("drums").leaf_name());
     }
 }
@@ -0,0 +1,41 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::signature::{Algorithm, Scope, Signature};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+// Unique path in temporary directory that is deleted along with anything written to it
+// when dropped so that nothing is left behind when an assertion fails
+pub struct TempPath(PathBuf);
+
+impl TempPath {
+    pub fn new(name: &str) -> Self {
+        Self(std::env::temp_dir().join(format!(
+            "tagger-{}-{}-{}",
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::SeqCst),
+            name
+        )))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let _ = if self.0.is_dir() {
+            fs::remove_dir_all(&self.0)
+        } else {
+            fs::remove_file(&self.0)
+        };
+    }
+}
+
+// Distinct valid signature for each value of n
+pub fn make_signature(n: u8) -> Signature {
+    Signature::new(Algorithm::Sha256, Scope::File, vec![n; 32], n as u64)
+}
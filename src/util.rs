@@ -0,0 +1,10 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::result::Result;
+
+pub const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+// Current time in seconds since Unix epoch
+pub fn unix_time_now() -> Result<i64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64)
+}